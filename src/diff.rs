@@ -0,0 +1,156 @@
+use std::fmt;
+
+use super::items::Item;
+use super::utils::natural_cmp;
+
+/// A difference between two BOMs. Parts are named by designator, or by
+/// comment for the ones without designator, like screws or the PCB.
+#[derive(Debug, PartialEq)]
+pub enum BomChange {
+    /// Part and a summary of it: comment and footprint.
+    Added(String, String),
+    Removed(String, String),
+    Changed {
+        part: String,
+        field: String,
+        old: String,
+        new: String,
+    },
+}
+
+impl BomChange {
+    /// Designators, or comment, of the changed part.
+    pub fn part(&self) -> &str {
+        match self {
+            BomChange::Added(p, _) | BomChange::Removed(p, _) => p,
+            BomChange::Changed { part, .. } => part,
+        }
+    }
+}
+
+impl fmt::Display for BomChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BomChange::Added(p, s) => write!(f, "+ {} {}", p, s),
+            BomChange::Removed(p, s) => write!(f, "- {} {}", p, s),
+            BomChange::Changed {
+                part,
+                field,
+                old,
+                new,
+            } => write!(f, "~ {} {}: {} -> {}", part, field, old, new),
+        }
+    }
+}
+
+/// Part name, its occurrence for names repeated in the BOM, and the fields.
+type Part = (String, usize, Vec<(&'static str, String)>);
+
+/// Compared fields of each part, undesignated ones also by quantity.
+fn parts(items: &[Item]) -> Vec<Part> {
+    let mut parts: Vec<Part> = Vec::new();
+    let mut push = |name: &str, fields: Vec<(&'static str, String)>| {
+        let n = parts.iter().filter(|m| m.0 == name).count();
+        parts.push((name.to_string(), n, fields));
+    };
+    for item in items {
+        let mpn = item
+            .part_numbers
+            .iter()
+            .map(|m| m.mpn.as_str())
+            .filter(|m| !m.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let fields = vec![
+            ("comment", item.comment.clone()),
+            ("footprint", item.footprint.clone()),
+            ("description", item.description.clone()),
+            ("mpn", mpn),
+        ];
+        if item.designator.is_empty() {
+            let mut fields = fields;
+            fields.push(("quantity", item.quantity.to_string()));
+            push(&item.comment, fields);
+        } else {
+            for d in item.designator.iter() {
+                push(d, fields.clone());
+            }
+        }
+    }
+    parts
+}
+
+fn summary(fields: &[(&'static str, String)]) -> String {
+    format!("{} {}", fields[0].1, fields[1].1)
+        .trim()
+        .to_string()
+}
+
+/// Parts added, removed or changed from `old` to `new`, in designator order.
+pub fn compare(old: &[Item], new: &[Item]) -> Vec<BomChange> {
+    let (old, new) = (parts(old), parts(new));
+    let mut changes = Vec::new();
+    for (part, n, fields) in old.iter() {
+        match new.iter().find(|m| &m.0 == part && m.1 == *n) {
+            None => changes.push(BomChange::Removed(part.clone(), summary(fields))),
+            Some((_, _, new_fields)) => {
+                for ((field, o), (_, n)) in fields.iter().zip(new_fields.iter()) {
+                    if o != n {
+                        changes.push(BomChange::Changed {
+                            part: part.clone(),
+                            field: field.to_string(),
+                            old: o.clone(),
+                            new: n.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+    for (part, n, fields) in new.iter() {
+        if !old.iter().any(|m| &m.0 == part && m.1 == *n) {
+            changes.push(BomChange::Added(part.clone(), summary(fields)));
+        }
+    }
+    changes.sort_by(|a, b| natural_cmp(a.part(), b.part()));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::DataParser;
+    use crate::load::to_rows;
+
+    #[test]
+    fn test_compare() {
+        let old = DataParser::new(to_rows(&[
+            &["Designator", "Comment", "Footprint", "Quantity"],
+            &["R1, R2, R10", "10k", "0603", ""],
+            &["C1", "100nF", "0402", ""],
+            &["C1", "1nF", "0402", ""],
+            &["", "Screw M3", "", "4"],
+        ]))
+        .unwrap();
+        let new = DataParser::new(to_rows(&[
+            &["Designator", "Comment", "Footprint", "Quantity"],
+            &["R1, R10", "10k", "0603", ""],
+            &["R3", "4k7", "0603", ""],
+            &["C1", "1uF", "0402", ""],
+            &["", "Screw M3", "", "6"],
+        ]))
+        .unwrap();
+        let changes = compare(old.items(), new.items());
+        assert_eq!(
+            changes.iter().map(|m| m.to_string()).collect::<Vec<_>>(),
+            vec![
+                "~ C1 comment: 100nF -> 1uF",
+                "- C1 1nF 0402",
+                "- R2 10k 0603",
+                "+ R3 4k7 0603",
+                "~ Screw M3 quantity: 4 -> 6",
+            ]
+        );
+        assert!(compare(old.items(), old.items()).is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use serde_json::{Map, Value};
+use std::io::Write;
+
+use super::items::{Category, HeaderMap, Item};
+use super::template::{cell_value, Template};
+
+/// Output format of the text writers.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TextFormat {
+    Csv,
+    Json,
+    Markdown,
+    Text,
+}
+
+/// A table for each category, with the columns padded to the same width.
+///
+/// Markdown has a heading and a pipe table for each category, plain text the
+/// category name over the header row and a dashed rule.
+fn aligned_tables(
+    format: TextFormat,
+    labels: &[String],
+    rows: &[(&Category, Vec<String>)],
+) -> String {
+    let cell = |v: &str| match format {
+        TextFormat::Markdown => v.replace('|', "\\|"),
+        _ => v.to_string(),
+    };
+    let mut width: Vec<usize> = labels.iter().map(|m| m.chars().count()).collect();
+    for (_, values) in rows.iter() {
+        for (n, v) in values.iter().enumerate() {
+            width[n] = width[n].max(cell(v).chars().count());
+        }
+    }
+    let line = |values: &[String]| {
+        let padded = values
+            .iter()
+            .zip(width.iter())
+            .map(|(v, w)| format!("{:<w$}", cell(v), w = w))
+            .collect::<Vec<_>>();
+        match format {
+            TextFormat::Markdown => format!("| {} |\n", padded.join(" | ")),
+            _ => format!("{}\n", padded.join("  ").trim_end()),
+        }
+    };
+
+    let mut text = String::new();
+    let mut current: Option<&Category> = None;
+    for (c, values) in rows.iter() {
+        if current != Some(c) {
+            if current.is_some() {
+                text.push('\n');
+            }
+            current = Some(c);
+            let rule = width.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>();
+            match format {
+                TextFormat::Markdown => {
+                    text.push_str(&format!("## {:?}\n\n", c));
+                    text.push_str(&line(labels));
+                    text.push_str(&format!("|-{}-|\n", rule.join("-|-")));
+                }
+                _ => {
+                    text.push_str(&format!("{:?}\n", c));
+                    text.push_str(&line(labels));
+                    text.push_str(&format!("{}\n", rule.join("  ")));
+                }
+            }
+        }
+        text.push_str(&line(values));
+    }
+    text
+}
+
+/// Write the merged BOM as text, with the template columns and sorting.
+///
+/// CSV has a Category column before the template ones, in place of the
+/// category rows of the xlsx. JSON is an array with an object for each item.
+/// Markdown and plain text have an aligned table for each category.
+pub fn write_text<W: Write>(
+    mut out: W,
+    format: TextFormat,
+    template: &Template,
+    headers: &[HeaderMap],
+    data: &[Item],
+    categories: &[Category],
+) -> Result<(), String> {
+    let columns = template.columns(headers);
+    let columns = &columns;
+    let rows = categories
+        .iter()
+        .flat_map(|c| {
+            template.items_of(data, c).into_iter().map(move |item| {
+                let values = columns
+                    .iter()
+                    .map(|col| cell_value(item, headers, &col.source))
+                    .collect::<Vec<_>>();
+                (c, values)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let result = match format {
+        TextFormat::Csv => {
+            let mut wr = csv::Writer::from_writer(out);
+            let mut header = vec![String::from("Category")];
+            header.extend(columns.iter().map(|c| c.label.clone()));
+            wr.write_record(&header).and_then(|_| {
+                for (c, values) in rows.iter() {
+                    let mut record = vec![format!("{:?}", c)];
+                    record.extend(values.iter().cloned());
+                    wr.write_record(&record)?;
+                }
+                wr.flush().map_err(csv::Error::from)
+            })
+        }
+        .map_err(|e| e.to_string()),
+        TextFormat::Json => {
+            let items = rows
+                .iter()
+                .map(|(c, values)| {
+                    let mut obj = Map::new();
+                    obj.insert(String::from("Category"), Value::from(format!("{:?}", c)));
+                    for (col, v) in columns.iter().zip(values.iter()) {
+                        obj.insert(col.label.clone(), Value::from(v.as_str()));
+                    }
+                    Value::Object(obj)
+                })
+                .collect::<Vec<_>>();
+            serde_json::to_writer_pretty(out, &items).map_err(|e| e.to_string())
+        }
+        TextFormat::Markdown | TextFormat::Text => {
+            let labels = columns.iter().map(|c| c.label.clone()).collect::<Vec<_>>();
+            out.write_all(aligned_tables(format, &labels, &rows).as_bytes())
+                .map_err(|e| e.to_string())
+        }
+    };
+    result.map_err(|error| format!("Unable to write output: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::DataParser;
+    use crate::load::{InputFormat, Load};
+
+    #[test]
+    fn test_csv_round_trip() {
+        let csv = "Quantity,Designator,Comment,Footprint\n\
+                   2,\"R1, R2\",10k,0603\n\
+                   1,C1,100nF,0402\n\
+                   1,R3,4k7,0603\n";
+        let data = DataParser::new(Load::from_reader(csv.as_bytes(), None).unwrap()).unwrap();
+        let mut out = Vec::new();
+        write_text(
+            &mut out,
+            TextFormat::Csv,
+            &Template::default(),
+            data.headers(),
+            data.items(),
+            &data.categories(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Category,Qty,Designator,Comment,Footprint\n\
+             Resistors,1,R3,4k7,0603\n\
+             Resistors,2,\"R1, R2\",10k,0603\n\
+             Capacitors,1,C1,100nF,0402\n"
+        );
+
+        let data =
+            DataParser::new(Load::from_reader(csv.as_bytes(), Some(InputFormat::Csv)).unwrap())
+                .unwrap();
+        let mut out = Vec::new();
+        write_text(
+            &mut out,
+            TextFormat::Json,
+            &Template::default(),
+            data.headers(),
+            data.items(),
+            &data.categories(),
+        )
+        .unwrap();
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json[2]["Designator"], "C1");
+        assert_eq!(json[2]["Category"], "Capacitors");
+    }
+
+    #[test]
+    fn test_aligned_tables() {
+        let csv = "Designator,Comment,Footprint\n\
+                   \"R1, R2\",10k,0603\n\
+                   C1,100nF,0402\n\
+                   U1,LM1117 | 3V3,SOT223\n";
+        let data = DataParser::new(Load::from_reader(csv.as_bytes(), None).unwrap()).unwrap();
+        let write = |format| {
+            let mut out = Vec::new();
+            write_text(
+                &mut out,
+                format,
+                &Template::default(),
+                data.headers(),
+                data.items(),
+                &data.categories(),
+            )
+            .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            write(TextFormat::Markdown),
+            "## Resistors\n\n\
+             | Qty | Designator | Comment       | Footprint |\n\
+             |-----|------------|---------------|-----------|\n\
+             | 2   | R1, R2     | 10k           | 0603      |\n\
+             \n\
+             ## Capacitors\n\n\
+             | Qty | Designator | Comment       | Footprint |\n\
+             |-----|------------|---------------|-----------|\n\
+             | 1   | C1         | 100nF         | 0402      |\n\
+             \n\
+             ## IC\n\n\
+             | Qty | Designator | Comment       | Footprint |\n\
+             |-----|------------|---------------|-----------|\n\
+             | 1   | U1         | LM1117 \\| 3V3 | SOT223    |\n"
+        );
+        assert_eq!(
+            write(TextFormat::Text),
+            "Resistors\n\
+             Qty  Designator  Comment       Footprint\n\
+             ---  ----------  ------------  ---------\n\
+             2    R1, R2      10k           0603\n\
+             \n\
+             Capacitors\n\
+             Qty  Designator  Comment       Footprint\n\
+             ---  ----------  ------------  ---------\n\
+             1    C1          100nF         0402\n\
+             \n\
+             IC\n\
+             Qty  Designator  Comment       Footprint\n\
+             ---  ----------  ------------  ---------\n\
+             1    U1          LM1117 | 3V3  SOT223\n"
+        );
+    }
+}
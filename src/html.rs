@@ -0,0 +1,185 @@
+use std::io::Write;
+
+use super::items::{Category, HeaderMap, Item};
+use super::metadata::Metadata;
+use super::stats::{Counts, StatsReport};
+use super::template::{cell_value, parse_color, Template};
+use super::utils::escape_xml;
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; font-size: 14px; margin: 2em; }
+table { border-collapse: collapse; margin: 0.5em 0 1.5em 0; }
+th, td { border: 1px solid #bbb; padding: 3px 8px; text-align: left; vertical-align: top; }
+th { background: HEADER_BG; }
+td.num, th.num { text-align: right; }
+summary { font-size: 1.1em; font-weight: bold; cursor: pointer; margin-top: 0.5em; }
+tr.dnp td { background: #eeeeee; color: #888888; text-decoration: line-through; }
+tr.unknown td { background: #ffe0b0; }
+#search { font-size: 1em; padding: 4px; width: 30em; margin-bottom: 1em; }
+.legend span { padding: 2px 8px; margin-right: 1em; }
+"#;
+
+const SCRIPT: &str = r#"
+function filterBom(query) {
+  query = query.toLowerCase();
+  document.querySelectorAll('details.category').forEach(function (section) {
+    var shown = 0;
+    section.querySelectorAll('tr.item').forEach(function (row) {
+      var match = row.textContent.toLowerCase().indexOf(query) >= 0;
+      row.style.display = match ? '' : 'none';
+      if (match) { shown++; }
+    });
+    section.style.display = shown > 0 ? '' : 'none';
+    if (query) { section.open = true; }
+  });
+}
+"#;
+
+fn counts_row(label: &str, c: &Counts) -> String {
+    format!(
+        "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>\n",
+        escape_xml(label),
+        c.lines,
+        c.placements,
+        c.smd,
+        c.tht,
+        c.top,
+        c.bottom
+    )
+}
+
+/// Write a self-contained HTML page to review the merged BOM: stats, a
+/// search box and a collapsible table for each category. Not populated parts
+/// and parts without value or category are highlighted.
+pub fn write_html<W: Write>(
+    mut out: W,
+    template: &Template,
+    metadata: &Metadata,
+    headers: &[HeaderMap],
+    data: &[Item],
+    categories: &[Category],
+) -> Result<(), String> {
+    let columns = template.columns(headers);
+    let title = match metadata.project.as_str() {
+        "" => String::from("Bill Of Materials"),
+        p => format!("Bill Of Materials - {}", p),
+    };
+    let header_bg = match &template.header.bg_color {
+        Some(c) => parse_color(c)?,
+        None => String::from("#dddddd"),
+    };
+
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n<script>{}</script>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape_xml(&title),
+        STYLE.replace("HEADER_BG", &header_bg),
+        SCRIPT,
+        escape_xml(&title)
+    );
+
+    let mut info = String::new();
+    for (label, value) in [
+        ("Revision", metadata.revision.clone()),
+        ("Variant", metadata.variant.clone()),
+        ("Date", metadata.date()),
+        ("Author", metadata.author.clone()),
+    ]
+    .iter()
+    {
+        if !value.is_empty() {
+            info.push_str(&format!("<b>{}</b> {} &nbsp; ", label, escape_xml(value)));
+        }
+    }
+    page.push_str(&format!("<p>{}</p>\n", info));
+
+    let report = StatsReport::new(data);
+    page.push_str("<table>\n<tr><th>Category</th><th class=\"num\">Lines</th><th class=\"num\">Placements</th><th class=\"num\">SMD</th><th class=\"num\">THT</th><th class=\"num\">Top</th><th class=\"num\">Bottom</th></tr>\n");
+    for c in report.categories.iter() {
+        page.push_str(&counts_row(&format!("{:?}", c.category), &c.counts));
+    }
+    page.push_str(&counts_row("Total", &report.total));
+    page.push_str("</table>\n");
+
+    page.push_str("<input id=\"search\" type=\"search\" placeholder=\"Search designator, value, part number..\" oninput=\"filterBom(this.value)\">\n");
+    page.push_str("<p class=\"legend\"><span class=\"dnp\" style=\"background: #eeeeee; color: #888888; text-decoration: line-through;\">Not populated</span><span style=\"background: #ffe0b0;\">Unknown value or category</span></p>\n");
+
+    for c in categories.iter() {
+        let items = template.items_of(data, c);
+        let parts: usize = items.iter().map(|m| m.quantity).sum();
+        page.push_str(&format!(
+            "<details class=\"category\" open>\n<summary>{:?} ({} lines, {} parts)</summary>\n<table>\n<tr>",
+            c,
+            items.len(),
+            parts
+        ));
+        for col in columns.iter() {
+            page.push_str(&format!("<th>{}</th>", escape_xml(&col.label)));
+        }
+        page.push_str("</tr>\n");
+        for item in items {
+            let class = if !item.is_fitted() {
+                "item dnp"
+            } else if item.category == Category::Invalid || item.comment.is_empty() {
+                "item unknown"
+            } else {
+                "item"
+            };
+            page.push_str(&format!("<tr class=\"{}\">", class));
+            for col in columns.iter() {
+                page.push_str(&format!(
+                    "<td>{}</td>",
+                    escape_xml(&cell_value(item, headers, &col.source))
+                ));
+            }
+            page.push_str("</tr>\n");
+        }
+        page.push_str("</table>\n</details>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+
+    out.write_all(page.as_bytes())
+        .map_err(|error| format!("Unable to write html: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::DataParser;
+    use crate::load::to_rows;
+
+    #[test]
+    fn test_html_report() {
+        let rows = to_rows(&[
+            &["Designator", "Comment", "Footprint"],
+            &["R1, R2", "10k", "0603"],
+            &["R3", "NP", "0603"],
+            &["U1", "<odd> & part", "SOT23"],
+            &["U2", "", "SOT23"],
+        ]);
+        let data = DataParser::new(rows).unwrap();
+        let metadata = Metadata {
+            project: String::from("Motor driver"),
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        write_html(
+            &mut out,
+            &Template::default(),
+            &metadata,
+            data.headers(),
+            data.items(),
+            &data.categories(),
+        )
+        .unwrap();
+        let page = String::from_utf8(out).unwrap();
+
+        assert!(page.contains("<title>Bill Of Materials - Motor driver</title>"));
+        assert!(page.contains("<summary>Resistors (2 lines, 3 parts)</summary>"));
+        assert!(page.contains("<tr class=\"item dnp\"><td>1</td><td>R3</td><td>NP</td>"));
+        assert!(page.contains("<tr class=\"item unknown\">"));
+        assert!(page.contains("&lt;odd&gt; &amp; part"));
+        // Usable offline, no external assets
+        assert!(!page.contains("src=") && !page.contains("href="));
+    }
+}
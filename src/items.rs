@@ -0,0 +1,1111 @@
+use lazy_static::lazy_static;
+use log::{debug, trace, warn};
+use regex::Regex;
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use super::load::RowSource;
+use super::utils::{
+    base_exp_to_value, convert_comment_to_value, designator_category, detect_measure_unit,
+    is_not_populated, natural_cmp,
+};
+
+/// Row where the Quantity column disagrees with the number of designators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantityMismatch {
+    /// Index, from zero, of the row in the source sheet.
+    pub row: usize,
+    pub designator: Vec<String>,
+    /// Quantity declared in the row.
+    pub quantity: usize,
+}
+
+/// Value of a "Code" or "Note" column, kept as it is in the output.
+#[derive(Debug, Clone)]
+pub struct ExtraCol {
+    pub label: Header,
+    pub value: String,
+}
+
+/// Kind of part, from the designator prefix. Items are grouped and written
+/// in this order, `Invalid` for unknown prefixes.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Eq, Ord, Serialize)]
+pub enum Category {
+    Connectors,
+    Mechanicals,
+    Fuses,
+    Resistors,
+    Capacitors,
+    Diode,
+    Inductors,
+    Transistor,
+    Transformes,
+    Cristal,
+    IC,
+    Invalid,
+}
+
+/// Known BOM column, matched on the header labels. `Extra` is for "Code"
+/// and "Note" columns.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Eq, Ord, Copy)]
+pub enum Header {
+    Quantity,
+    Designator,
+    Comment,
+    Footprint,
+    Description,
+    Manufacturer,
+    ManufacturerPartNumber,
+    MountTecnology,
+    Layer,
+    Extra,
+}
+
+/// Manufacturer and part number, one for each alternate source.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PartNumber {
+    pub manufacturer: String,
+    pub mpn: String,
+}
+
+/// Board side a part is placed on.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+pub enum Side {
+    Top,
+    Bottom,
+}
+
+/// Mount technology, `Mixed` when merged rows disagree.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+pub enum Mount {
+    Smd,
+    Tht,
+    Mixed,
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Side, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "top" | "t" | "top layer" | "toplayer" => Ok(Side::Top),
+            "bottom" | "bot" | "b" | "bottom layer" | "bottomlayer" => Ok(Side::Bottom),
+            _ => Err(format!("Invalid layer [{}]", s)),
+        }
+    }
+}
+
+impl FromStr for Mount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Mount, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "smd" | "smt" => Ok(Mount::Smd),
+            "tht" | "thd" | "through hole" => Ok(Mount::Tht),
+            "mixed" => Ok(Mount::Mixed),
+            _ => Err(format!("Invalid mount technology [{}]", s)),
+        }
+    }
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Display for Mount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mount::Smd => write!(f, "SMD"),
+            Mount::Tht => write!(f, "THT"),
+            Mount::Mixed => write!(f, "Mixed"),
+        }
+    }
+}
+
+/// Which fields are used to decide that two rows are the same part.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MergeKey {
+    /// Comment (value), footprint, description and extra columns.
+    Value,
+    /// First manufacturer part number, falling back to `Value` when missing.
+    PartNumber,
+}
+
+/// Field used to order items inside a category.
+#[derive(Debug, PartialEq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Parsed component value, not populated (NP) parts last.
+    Value,
+    Comment,
+    Footprint,
+    Description,
+    /// Natural order of the first designator, R2 before R10.
+    Designator,
+}
+
+/// Settings that drive how rows are turned into items.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Fields which make two rows the same part.
+    pub merge_key: MergeKey,
+    /// Category given to rows without designators, like screws, labels or the PCB.
+    pub undesignated: Category,
+    /// Category of designator prefixes, in place of the built in ones.
+    pub categories: Vec<(String, Category)>,
+    /// Boards built from the BOM, the quantities are multiplied by it.
+    pub multiplier: usize,
+    /// Merge the rows of the same part, false to keep them as in the source.
+    pub merge: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            merge_key: MergeKey::Value,
+            undesignated: Category::Mechanicals,
+            categories: Vec::new(),
+            multiplier: 1,
+            merge: true,
+        }
+    }
+}
+
+/// A BOM line: one part, with the designators of the rows merged into it.
+#[derive(Debug, Clone)]
+pub struct Item {
+    unique_id: String,
+    pub category: Category,
+    /// Value parsed from the comment as base and exponent, like (4.7, 3) for
+    /// 4k7. Negative base for not populated parts, NaN when not a number.
+    pub base_exp: (f32, i32),
+    /// Unit of the value, from the designator: "ohm", "F", "H" or "Hz".
+    pub measure_unit: String,
+    /// Parts to buy, the designators count times the board multiplier.
+    pub quantity: usize,
+    pub designator: Vec<String>,
+    /// Value or part name, as in the source.
+    pub comment: String,
+    pub footprint: String,
+    pub description: String,
+    /// Side of each designator, in the same order.
+    pub side: Vec<Option<Side>>,
+    pub mount: Option<Mount>,
+    /// Manufacturer part numbers, the first one is the preferred source.
+    pub part_numbers: Vec<PartNumber>,
+    /// Extra columns, in the order of the header.
+    pub extra: Vec<ExtraCol>,
+}
+
+/// A column found in the header row.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HeaderMap {
+    pub key: Header,
+    /// Label written in the output, like "Manufacturer 2".
+    pub label: String,
+    /// Column index, from zero, in the source sheet.
+    pub index: usize,
+}
+
+impl fmt::Debug for HeaderMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}-> {} {}", self.key, self.label, self.index)
+    }
+}
+
+/// A parsed BOM: the header found in the sheet and the merged items.
+pub struct DataParser {
+    items: Vec<Item>,
+    headers: Vec<HeaderMap>,
+    header_row: usize,
+    mismatches: Vec<QuantityMismatch>,
+}
+
+impl FromStr for Category {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Category, Self::Err> {
+        let all = [
+            Category::Connectors,
+            Category::Mechanicals,
+            Category::Fuses,
+            Category::Resistors,
+            Category::Capacitors,
+            Category::Diode,
+            Category::Inductors,
+            Category::Transistor,
+            Category::Transformes,
+            Category::Cristal,
+            Category::IC,
+        ];
+        match all
+            .iter()
+            .find(|m| format!("{:?}", m).eq_ignore_ascii_case(s.trim()))
+        {
+            Some(c) => Ok(c.clone()),
+            None => Err(format!("Invalid category [{}]", s)),
+        }
+    }
+}
+
+impl DataParser {
+    /// Parse and merge a BOM with the default options.
+    pub fn new<S: RowSource>(source: S) -> Result<DataParser, String> {
+        Self::with_options(source, &ParseOptions::default())
+    }
+
+    /// Parse a BOM from a `Load`, in-memory rows or any other `RowSource`.
+    /// Errors when no row looks like a BOM header.
+    pub fn with_options<S: RowSource>(
+        mut source: S,
+        options: &ParseOptions,
+    ) -> Result<DataParser, String> {
+        let rows = source.rows();
+
+        let (header_row, header_len, mut headers) = match Self::find_headers(&rows) {
+            Some(found) => found,
+            None => return Err(String::from("Unable to find header row")),
+        };
+
+        headers.sort_by_key(|m| m.key);
+        debug!("{:?}", headers);
+
+        let first_row = header_row + header_len;
+        let (data, mismatches) = Self::parse_data(&rows[first_row..], first_row, &headers, options);
+        let items = if options.merge {
+            Self::sets(data)
+        } else {
+            data
+        };
+
+        Ok(DataParser {
+            headers,
+            items,
+            header_row,
+            mismatches,
+        })
+    }
+
+    /// Combine the BOMs of more boards in one, with the columns of all of
+    /// them. With `merge` the same parts of different BOMs are merged too.
+    pub fn combine(parsers: Vec<DataParser>, merge: bool) -> DataParser {
+        let mut headers: Vec<HeaderMap> = Vec::new();
+        for p in parsers.iter() {
+            for h in p.headers.iter() {
+                if !headers.iter().any(|m| m.key == h.key && m.label == h.label) {
+                    headers.push(h.clone());
+                }
+            }
+        }
+        headers.sort_by_key(|m| m.key);
+
+        let extra_labels = |headers: &[HeaderMap]| {
+            headers
+                .iter()
+                .filter(|m| m.key == Header::Extra)
+                .map(|m| m.label.clone())
+                .collect::<Vec<_>>()
+        };
+        let labels = extra_labels(&headers);
+        let header_row = parsers.first().map_or(0, |p| p.header_row);
+        let mut items = Vec::new();
+        let mut mismatches = Vec::new();
+        for p in parsers {
+            // Extra values follow the columns of their own BOM, move them to the combined ones
+            let own = extra_labels(&p.headers);
+            for mut item in p.items {
+                let extra = labels
+                    .iter()
+                    .map(|l| ExtraCol {
+                        label: Header::Extra,
+                        value: own
+                            .iter()
+                            .position(|m| m == l)
+                            .and_then(|n| item.extra.get(n))
+                            .map_or(String::new(), |m| m.value.clone()),
+                    })
+                    .collect();
+                item.extra = extra;
+                items.push(item);
+            }
+            mismatches.extend(p.mismatches);
+        }
+
+        DataParser {
+            headers,
+            items: if merge { Self::sets(items) } else { items },
+            header_row,
+            mismatches,
+        }
+    }
+
+    /// Look for the row which best matches a BOM header and return its index,
+    /// the number of rows it spans and the header map.
+    ///
+    /// Every row is scored on the known columns it contains, so a title block
+    /// or a stray "Comment" above the table does not end up in the map. When
+    /// the row below the best one improves the score, without being a data row,
+    /// the header is considered split across the two rows.
+    fn find_headers(rows: &[Vec<String>]) -> Option<(usize, usize, Vec<HeaderMap>)> {
+        let mut best: Option<(usize, usize)> = None;
+        for (n, row) in rows.iter().enumerate() {
+            let score = Self::header_score(&Self::match_headers(row));
+            if score > 0 && !matches!(best, Some((_, s)) if s >= score) {
+                best = Some((n, score));
+            }
+        }
+
+        let (row, score) = best?;
+        let headers = Self::match_headers(&rows[row]);
+
+        if let Some(next) = rows.get(row + 1) {
+            let joined = rows[row]
+                .iter()
+                .enumerate()
+                .map(|(n, upper)| match next.get(n) {
+                    Some(lower) if !lower.trim().is_empty() => {
+                        format!("{} {}", upper.trim(), lower.trim())
+                            .trim()
+                            .to_string()
+                    }
+                    _ => upper.clone(),
+                })
+                .collect::<Vec<_>>();
+            let joined_headers = Self::match_headers(&joined);
+
+            let is_data = headers
+                .iter()
+                .filter(|m| m.key == Header::Designator)
+                .any(|m| next.get(m.index).is_some_and(|v| !v.trim().is_empty()));
+
+            if !is_data && Self::header_score(&joined_headers) > score {
+                return Some((row, 2, joined_headers));
+            }
+        }
+
+        Some((row, 1, headers))
+    }
+
+    fn header_score(headers: &[HeaderMap]) -> usize {
+        let mut score = 0;
+        for (n, hdr) in headers.iter().enumerate() {
+            // Repeated columns, like manufacturer alternates, count once
+            if hdr.key != Header::Extra && headers[..n].iter().any(|m| m.key == hdr.key) {
+                continue;
+            }
+            score += match hdr.key {
+                Header::Designator => 4,
+                Header::Extra => 1,
+                _ => 2,
+            };
+        }
+        score
+    }
+
+    fn match_headers(row: &[String]) -> Vec<HeaderMap> {
+        row.iter()
+            .enumerate()
+            .filter_map(|(n, col)| Self::match_header(col.trim(), n))
+            .collect()
+    }
+
+    pub(crate) fn match_header(col: &str, index: usize) -> Option<HeaderMap> {
+        lazy_static! {
+            static ref RE_NOTE: Regex = Regex::new(r"NOTE\s(.*)").unwrap();
+            static ref RE_CODE: Regex = Regex::new(r"CODE\s(.*)").unwrap();
+            static ref RE_MPN: Regex =
+                Regex::new(r"^(?:manufacturer[\s_]?part[\s_]?number|mpn)\s*(\d*)$").unwrap();
+            static ref RE_MANUFACTURER: Regex =
+                Regex::new(r"^(?:manufacturer|mfr)\s*(\d*)$").unwrap();
+        }
+
+        let (key, label) = match col.to_lowercase().as_str() {
+            "quantity" | "qty" | "qta" => (Header::Quantity, String::from("Quantity")),
+            "designator" => (Header::Designator, String::from("Designator")),
+            "comment" => (Header::Comment, String::from("Comment")),
+            "footprint" => (Header::Footprint, String::from("Footprint")),
+            "description" => (Header::Description, String::from("Description")),
+            "mounttechnology" | "mount_technology" | "mount technology" => {
+                (Header::MountTecnology, String::from("Mount Technology"))
+            }
+            "layer" => (Header::Layer, String::from("Layer")),
+            lower if RE_MPN.is_match(lower) => (
+                Header::ManufacturerPartNumber,
+                Self::alternate_label("Manufacturer Part Number", &RE_MPN, lower),
+            ),
+            lower if RE_MANUFACTURER.is_match(lower) => (
+                Header::Manufacturer,
+                Self::alternate_label("Manufacturer", &RE_MANUFACTURER, lower),
+            ),
+            _ => {
+                if let Some(m) = RE_CODE.captures(col).and_then(|cc| cc.get(1)) {
+                    (Header::Extra, format!("Code {:}", m.as_str()))
+                } else if let Some(m) = RE_NOTE.captures(col).and_then(|cc| cc.get(1)) {
+                    (Header::Extra, format!("Note {:}", m.as_str()))
+                } else {
+                    return None;
+                }
+            }
+        };
+
+        Some(HeaderMap { key, label, index })
+    }
+
+    /// Keep the alternate number, if any, of "Manufacturer 2" like columns.
+    fn alternate_label(name: &str, re: &Regex, col: &str) -> String {
+        match re.captures(col).and_then(|cc| cc.get(1)) {
+            Some(m) if !m.as_str().is_empty() => format!("{} {}", name, m.as_str()),
+            _ => String::from(name),
+        }
+    }
+
+    /// Index, from zero, of the row used as header in the source sheet.
+    pub fn header_row(&self) -> usize {
+        self.header_row
+    }
+
+    /// Columns of the header, sorted by kind.
+    pub fn headers(&self) -> &[HeaderMap] {
+        &self.headers
+    }
+
+    /// Items in the order of their first row in the source.
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// Rows where the Quantity column does not match the designators count.
+    pub fn quantity_mismatches(&self) -> &[QuantityMismatch] {
+        &self.mismatches
+    }
+
+    /// Categories of the items, sorted.
+    pub fn categories(&self) -> Vec<Category> {
+        let mut cat: Vec<Category> = Vec::new();
+        for c in &self.items {
+            if !cat.contains(&c.category) {
+                cat.push(c.category.clone());
+            }
+        }
+        cat.sort();
+        cat
+    }
+
+    fn parse_data(
+        rows: &[Vec<String>],
+        first_row: usize,
+        headers: &[HeaderMap],
+        options: &ParseOptions,
+    ) -> (Vec<Item>, Vec<QuantityMismatch>) {
+        let mut items = Vec::new();
+        let mut mismatches = Vec::new();
+
+        for (n, row) in rows.iter().enumerate() {
+            // Blank rows, like the ones closing a sheet, are not data
+            if row.iter().all(|m| m.trim().is_empty()) {
+                continue;
+            }
+
+            /* Find data in source with column position find above */
+            let mut template = Item {
+                unique_id: String::new(),
+                category: Category::Invalid,
+                base_exp: (0.0, 0),
+                measure_unit: String::new(),
+                quantity: 0,
+                designator: vec![],
+                comment: String::new(),
+                footprint: String::new(),
+                description: String::new(),
+                side: vec![],
+                mount: None,
+                part_numbers: vec![],
+                extra: vec![],
+            };
+
+            let mut side: Option<Side> = None;
+            let mut skip_row = false;
+            let mut quantity: Option<usize> = None;
+            let mut missing = 0;
+            for header_label in headers {
+                match row.get(header_label.index) {
+                    Some(value) => match header_label.key {
+                        Header::Quantity => {
+                            quantity = match value.trim().parse::<f32>() {
+                                Ok(q) if q >= 0.0 => Some(q.round() as usize),
+                                _ => None,
+                            };
+                        }
+                        Header::Designator => {
+                            // this row contain a header, so we should skip it.
+                            if value == "Designator" {
+                                trace!("skip: [{:?}]", value);
+                                skip_row = true;
+                                continue;
+                            }
+                            if value.trim().is_empty() {
+                                continue;
+                            }
+                            template.designator = value
+                                .split(',')
+                                .map(|m| m.trim().to_string())
+                                .collect::<Vec<_>>();
+
+                            let des = template.designator.first().unwrap();
+                            template.category =
+                                designator_category(des.trim(), &options.categories);
+                            template.measure_unit = detect_measure_unit(des.trim());
+                        }
+                        Header::Comment => {
+                            template.comment = value.clone();
+                            // Only designated parts carry a value, "M3x10" is not 3 MOhm
+                            if !template.designator.is_empty() {
+                                template.base_exp = convert_comment_to_value(value);
+                            }
+                        }
+                        Header::Description => {
+                            template.description = value.clone();
+                        }
+                        Header::Footprint => {
+                            template.footprint = value.clone();
+                        }
+                        Header::Layer => {
+                            side = value.parse().ok();
+                        }
+                        Header::MountTecnology => {
+                            template.mount = value.parse().ok();
+                        }
+                        Header::Manufacturer | Header::ManufacturerPartNumber => {
+                            // n-th Manufacturer column pairs with the n-th part number column
+                            let alternate = headers
+                                .iter()
+                                .filter(|m| m.key == header_label.key)
+                                .position(|m| m.index == header_label.index)
+                                .unwrap();
+                            if template.part_numbers.len() <= alternate {
+                                template
+                                    .part_numbers
+                                    .resize(alternate + 1, PartNumber::default());
+                            }
+                            let pn = &mut template.part_numbers[alternate];
+                            if header_label.key == Header::Manufacturer {
+                                pn.manufacturer = value.trim().to_string();
+                            } else {
+                                pn.mpn = value.trim().to_string();
+                            }
+                        }
+                        _ => {
+                            template.extra.push(ExtraCol {
+                                label: header_label.key,
+                                value: value.clone(),
+                            });
+                        }
+                    },
+                    None => missing += 1,
+                }
+            }
+            if missing > 0 {
+                warn!(
+                    "Row {} misses {} of the header columns, left empty",
+                    first_row + n + 1,
+                    missing
+                );
+            }
+
+            if template.designator.is_empty() {
+                // Without designators only an explicit quantity keeps the row.
+                match quantity {
+                    Some(q) if q > 0 => {
+                        template.quantity = q;
+                        template.category = options.undesignated.clone();
+                    }
+                    _ => skip_row = true,
+                }
+            } else {
+                template.quantity = template.designator.len();
+                template.side = vec![side; template.quantity];
+                if let Some(q) = quantity {
+                    if q != template.quantity {
+                        mismatches.push(QuantityMismatch {
+                            row: first_row + n,
+                            designator: template.designator.clone(),
+                            quantity: q,
+                        });
+                    }
+                }
+            }
+
+            if !skip_row {
+                template.quantity *= options.multiplier;
+                template.unique_id = Self::unique_id(&template, options.merge_key);
+                items.push(template);
+            }
+        }
+        (items, mismatches)
+    }
+
+    fn unique_id(item: &Item, merge_key: MergeKey) -> String {
+        if merge_key == MergeKey::PartNumber {
+            if let Some(pn) = item.part_numbers.iter().find(|m| !m.mpn.is_empty()) {
+                return format!("MPN:{}", pn.mpn);
+            }
+        }
+
+        let mut ext_str: String = String::new();
+        for ext in item.extra.iter() {
+            ext_str = format!("{}{}", ext_str, ext.value);
+        }
+
+        match item.category {
+            Category::Connectors => format!("{}{}{}", item.footprint, item.description, ext_str),
+            _ => format!(
+                "{}{}{}{}",
+                item.comment, item.footprint, item.description, ext_str
+            ),
+        }
+    }
+
+    fn sets(data: Vec<Item>) -> Vec<Item> {
+        let mut items: Vec<Item> = Vec::new();
+
+        for row in data {
+            match items.iter().position(|m| m.unique_id == row.unique_id) {
+                Some(cc) => {
+                    let mut des: Vec<String> = Vec::new();
+                    des.append(&mut items[cc].designator.clone());
+                    des.append(&mut row.designator.clone());
+                    items[cc].designator = des;
+                    items[cc].quantity += row.quantity;
+                    items[cc].side.append(&mut row.side.clone());
+                    items[cc].mount = match (items[cc].mount, row.mount) {
+                        (Some(a), Some(b)) if a != b => Some(Mount::Mixed),
+                        (a, b) => a.or(b),
+                    };
+
+                    for pn in row.part_numbers {
+                        if !items[cc].part_numbers.contains(&pn) {
+                            items[cc].part_numbers.push(pn);
+                        }
+                    }
+
+                    //TODO: Merge all columns
+                }
+                _ => items.push(Item { ..row }),
+            }
+        }
+
+        items
+    }
+}
+
+impl Item {
+    /// False for not populated parts, with a comment starting with NP or DNP.
+    pub fn is_fitted(&self) -> bool {
+        !is_not_populated(&self.comment)
+    }
+
+    /// Number of parts placed on the given side, with the board multiplier.
+    pub fn placements(&self, side: Side) -> usize {
+        let count = self.side.iter().filter(|m| **m == Some(side)).count();
+        if count == 0 {
+            return 0;
+        }
+        count * self.quantity / self.designator.len()
+    }
+
+    /// Copy of the item with only the designators on the given side.
+    pub fn on_side(&self, side: Side) -> Option<Item> {
+        let designator = self
+            .designator
+            .iter()
+            .zip(self.side.iter())
+            .filter(|(_, s)| **s == Some(side))
+            .map(|(d, _)| d.clone())
+            .collect::<Vec<_>>();
+        if designator.is_empty() {
+            return None;
+        }
+        Some(Item {
+            // Keep the multiplier of the quantity
+            quantity: designator.len() * self.quantity / self.designator.len(),
+            side: vec![Some(side); designator.len()],
+            designator,
+            ..self.clone()
+        })
+    }
+
+    /// Order two items by the given keys, in sequence until one differs.
+    pub fn compare(&self, other: &Item, keys: &[SortKey]) -> Ordering {
+        for key in keys {
+            let ord = match key {
+                SortKey::Value => {
+                    // Values which do not parse go after the others
+                    let value = |i: &Item| base_exp_to_value(i.base_exp.0, i.base_exp.1);
+                    let rank = |i: &Item| (!i.is_fitted(), i.base_exp.0.is_nan());
+                    rank(self).cmp(&rank(other)).then_with(|| {
+                        value(self)
+                            .partial_cmp(&value(other))
+                            .unwrap_or(Ordering::Equal)
+                    })
+                }
+                SortKey::Comment => natural_cmp(&self.comment, &other.comment),
+                SortKey::Footprint => natural_cmp(&self.footprint, &other.footprint),
+                SortKey::Description => natural_cmp(&self.description, &other.description),
+                SortKey::Designator => {
+                    let first = |i: &Item| {
+                        i.designator
+                            .iter()
+                            .min_by(|a, b| natural_cmp(a, b))
+                            .cloned()
+                            .unwrap_or_default()
+                    };
+                    natural_cmp(&first(self), &first(other))
+                }
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl PartialEq for Item {
+    fn eq(&self, other: &Self) -> bool {
+        self.unique_id == other.unique_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::{to_rows, Load};
+    #[test]
+    fn test_find_headers() {
+        let boms = [
+            "test_data/test0.xlsx",
+            "test_data/test1.xlsx",
+            "test_data/test2.xlsx",
+        ];
+
+        let header_map_check = (
+            [
+                (Header::Quantity, "Quantity", 6),
+                (Header::Designator, "Designator", 3),
+                (Header::Comment, "Comment", 1),
+                (Header::Footprint, "Footprint", 4),
+                (Header::Description, "Description", 2),
+            ],
+            [
+                (Header::Quantity, "Quantity", 0),
+                (Header::Designator, "Designator", 1),
+                (Header::Comment, "Comment", 2),
+                (Header::Footprint, "Footprint", 3),
+                (Header::Description, "Description", 4),
+                (Header::Extra, "Code farnell", 5),
+                (Header::Extra, "Note produzione", 6),
+                (Header::Extra, "Code digikey", 7),
+            ],
+            [
+                (Header::Quantity, "Quantity", 8),
+                (Header::Designator, "Designator", 5),
+                (Header::Comment, "Comment", 2),
+                (Header::Footprint, "Footprint", 3),
+                (Header::Description, "Description", 0),
+            ],
+        );
+
+        let data: DataParser = DataParser::new(Load::new(boms[0]).unwrap()).unwrap();
+        assert_eq!(data.header_row(), 1);
+        let hdr_map: &[HeaderMap] = data.headers();
+        assert_eq!(hdr_map.len(), header_map_check.0.len());
+        for (n, i) in hdr_map.iter().enumerate() {
+            assert_eq!(i.key, header_map_check.0[n].0);
+            assert_eq!(i.label, header_map_check.0[n].1);
+            assert_eq!(i.index, header_map_check.0[n].2);
+        }
+
+        let data: DataParser = DataParser::new(Load::new(boms[1]).unwrap()).unwrap();
+        assert_eq!(data.header_row(), 0);
+        let hdr_map: &[HeaderMap] = data.headers();
+        assert_eq!(hdr_map.len(), header_map_check.1.len());
+        for (n, i) in hdr_map.iter().enumerate() {
+            assert_eq!(i.key, header_map_check.1[n].0);
+            assert_eq!(i.label, header_map_check.1[n].1);
+            assert_eq!(i.index, header_map_check.1[n].2);
+        }
+
+        let data: DataParser = DataParser::new(Load::new(boms[2]).unwrap()).unwrap();
+        let hdr_map: &[HeaderMap] = data.headers();
+        assert_eq!(hdr_map.len(), header_map_check.2.len());
+        for (n, i) in hdr_map.iter().enumerate() {
+            assert_eq!(i.key, header_map_check.2[n].0);
+            assert_eq!(i.label, header_map_check.2[n].1);
+            assert_eq!(i.index, header_map_check.2[n].2);
+        }
+    }
+
+    #[test]
+    fn test_find_headers_preamble() {
+        // Title block with a stray "Comment" above the real table
+        let rows = to_rows(&[
+            &["Project", "Comment", "", ""],
+            &["", "", "", ""],
+            &["Designator", "Comment", "Footprint", "Description"],
+            &["R1", "10k", "0603", "Resistor"],
+        ]);
+        let (row, len, hdr) = DataParser::find_headers(&rows).unwrap();
+        assert_eq!((row, len), (2, 1));
+        assert_eq!(hdr.len(), 4);
+        assert_eq!(hdr[1].key, Header::Comment);
+        assert_eq!(hdr[1].index, 1);
+
+        // Header split across two rows
+        let rows = to_rows(&[
+            &["Designator", "Comment", "Manufacturer", "Manufacturer"],
+            &["", "", "", "Part Number"],
+            &["R1", "10k", "Yageo", "RC0603FR-0710KL"],
+        ]);
+        let (row, len, hdr) = DataParser::find_headers(&rows).unwrap();
+        assert_eq!((row, len), (0, 2));
+        assert_eq!(hdr[3].key, Header::ManufacturerPartNumber);
+
+        // A data row below the header is never joined to it
+        let rows = to_rows(&[
+            &["Designator", "Comment", "Footprint"],
+            &["Layer", "10k", "0603"],
+        ]);
+        let (row, len, _) = DataParser::find_headers(&rows).unwrap();
+        assert_eq!((row, len), (0, 1));
+
+        assert!(DataParser::find_headers(&to_rows(&[&["a", "b"]])).is_none());
+    }
+
+    #[test]
+    fn test_quantity_check() {
+        let headers = vec![
+            HeaderMap {
+                key: Header::Quantity,
+                label: String::from("Quantity"),
+                index: 0,
+            },
+            HeaderMap {
+                key: Header::Designator,
+                label: String::from("Designator"),
+                index: 1,
+            },
+            HeaderMap {
+                key: Header::Description,
+                label: String::from("Description"),
+                index: 2,
+            },
+        ];
+        let rows = to_rows(&[
+            &["2", "R1, R2", "Resistor"],
+            &["3", "C1, C2", "Capacitor"],
+            &["4", "", "Screw M3"],
+            &["", "", ""],
+            &["", "U1", "Regulator"],
+        ]);
+
+        let (items, mismatches) =
+            DataParser::parse_data(&rows, 5, &headers, &ParseOptions::default());
+        assert_eq!(items.len(), 4);
+        assert_eq!(
+            items.iter().map(|m| m.quantity).collect::<Vec<_>>(),
+            vec![2, 2, 4, 1]
+        );
+        assert!(items[2].designator.is_empty());
+        assert_eq!(items[2].category, Category::Mechanicals);
+
+        assert_eq!(
+            mismatches,
+            vec![QuantityMismatch {
+                row: 6,
+                designator: vec![String::from("C1"), String::from("C2")],
+                quantity: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_from_rows() {
+        let rows = to_rows(&[
+            &["Project X", ""],
+            &["Designator", "Comment", "Footprint"],
+            &["R1", "10k", "0603"],
+            &["R2", "10k", "0603"],
+            &["C1", "100nF", "0402"],
+        ]);
+
+        let data = DataParser::new(rows.clone()).unwrap();
+        assert_eq!(data.header_row(), 1);
+        assert_eq!(data.items().len(), 2);
+        assert_eq!(data.items()[0].designator, vec!["R1", "R2"]);
+        assert_eq!(data.items()[0].quantity, 2);
+        assert_eq!(
+            data.categories(),
+            vec![Category::Resistors, Category::Capacitors]
+        );
+
+        let options = ParseOptions {
+            multiplier: 3,
+            ..Default::default()
+        };
+        let data = DataParser::with_options(rows.clone(), &options).unwrap();
+        assert_eq!(data.items()[0].quantity, 6);
+        assert_eq!(data.items()[1].quantity, 3);
+
+        let sided = to_rows(&[
+            &["Designator", "Comment", "Layer"],
+            &["R1", "10k", "Top"],
+            &["R2", "10k", "Bottom"],
+        ]);
+        let data = DataParser::with_options(sided, &options).unwrap();
+        let item = &data.items()[0];
+        assert_eq!(item.quantity, 6);
+        assert_eq!(item.placements(Side::Top), 3);
+        assert_eq!(item.placements(Side::Bottom), 3);
+
+        let options = ParseOptions {
+            merge: false,
+            ..Default::default()
+        };
+        let data = DataParser::with_options(rows, &options).unwrap();
+        assert_eq!(data.items().len(), 3);
+
+        // Blank rows are skipped, short ones keep the columns they have
+        let ragged = vec![
+            vec![
+                String::from("Designator"),
+                String::from("Comment"),
+                String::from("Footprint"),
+            ],
+            vec![String::new(), String::new(), String::new()],
+            vec![String::from("R1"), String::from("10k")],
+            vec![],
+        ];
+        let data = DataParser::new(ragged).unwrap();
+        assert_eq!(data.items().len(), 1);
+        assert_eq!(data.items()[0].comment, "10k");
+        assert_eq!(data.items()[0].footprint, "");
+
+        // Bad input is an error, not a panic
+        assert!(DataParser::new(vec![vec![String::from("a")]]).is_err());
+        assert!(Load::from_bytes(b"PK\x03\x04 broken".to_vec(), None).is_err());
+    }
+
+    #[test]
+    fn test_combine() {
+        let power = DataParser::new(to_rows(&[
+            &["Designator", "Comment", "Footprint", "CODE farnell"],
+            &["R1, R2", "10k", "0603", ""],
+            &["U1", "LM1117", "SOT223", "456"],
+        ]))
+        .unwrap();
+        let control = DataParser::with_options(
+            to_rows(&[
+                &["Designator", "Comment", "Footprint", "NOTE assembly"],
+                &["R3", "10k", "0603", ""],
+                &["C1", "100nF", "0402", "glue"],
+            ]),
+            &ParseOptions {
+                multiplier: 2,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let data = DataParser::combine(vec![power, control], true);
+        assert_eq!(
+            data.headers()
+                .iter()
+                .map(|m| m.label.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Designator",
+                "Comment",
+                "Footprint",
+                "Code farnell",
+                "Note assembly"
+            ]
+        );
+        assert_eq!(data.items().len(), 3);
+        assert_eq!(data.items()[0].designator, vec!["R1", "R2", "R3"]);
+        assert_eq!(data.items()[0].quantity, 4);
+        assert_eq!(data.items()[1].extra[0].value, "456");
+        assert_eq!(data.items()[2].quantity, 2);
+        assert_eq!(data.items()[2].extra[0].value, "");
+        assert_eq!(data.items()[2].extra[1].value, "glue");
+    }
+
+    #[test]
+    fn test_sort_value() {
+        let rows = to_rows(&[
+            &["Designator", "Comment"],
+            &["R1", "NP (0R)"],
+            &["R2", "10k"],
+            &["R3", "0R"],
+            &["R4", "1k"],
+        ]);
+        let data = DataParser::new(rows).unwrap();
+        let mut items = data.items().to_vec();
+        items.sort_by(|a, b| a.compare(b, &[SortKey::Value]));
+        assert_eq!(
+            items
+                .iter()
+                .map(|m| m.designator[0].as_str())
+                .collect::<Vec<_>>(),
+            vec!["R3", "R4", "R2", "R1"]
+        );
+    }
+
+    #[test]
+    fn test_merge_key() {
+        let mut item = Item {
+            unique_id: String::new(),
+            category: Category::IC,
+            base_exp: (0.0, 0),
+            measure_unit: String::new(),
+            quantity: 1,
+            designator: vec![String::from("U1")],
+            comment: String::from("LM75"),
+            footprint: String::from("SOIC8"),
+            description: String::from("Temp sensor"),
+            side: vec![None],
+            mount: None,
+            part_numbers: vec![],
+            extra: vec![],
+        };
+
+        // No part number, fall back to the component value
+        assert_eq!(
+            DataParser::unique_id(&item, MergeKey::PartNumber),
+            DataParser::unique_id(&item, MergeKey::Value)
+        );
+
+        item.part_numbers = vec![
+            PartNumber {
+                manufacturer: String::from("TI"),
+                mpn: String::new(),
+            },
+            PartNumber {
+                manufacturer: String::from("NXP"),
+                mpn: String::from("LM75BD"),
+            },
+        ];
+        assert_eq!(
+            DataParser::unique_id(&item, MergeKey::PartNumber),
+            "MPN:LM75BD"
+        );
+        assert_eq!(
+            DataParser::unique_id(&item, MergeKey::Value),
+            "LM75SOIC8Temp sensor"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use super::items::{DataParser, Header};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+#[derive(Default)]
+struct Component {
+    designator: String,
+    value: String,
+    footprint: String,
+    description: String,
+    datasheet: String,
+    fields: Vec<(String, String)>,
+}
+
+/// True for the content of a KiCad XML netlist or intermediate BOM.
+pub fn is_kicad_xml(content: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&content[..content.len().min(512)]);
+    head.trim_start().starts_with("<?xml") && head.contains("<export")
+}
+
+/// Rows of a KiCad eeschema XML export, one for each component.
+///
+/// Custom fields keep their name when it is a known BOM column, like
+/// Manufacturer or MPN, the others become `NOTE <name>` extra columns,
+/// as the datasheet does when set.
+pub fn xml_rows(content: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let mut reader = Reader::from_reader(content);
+    reader.trim_text(true);
+
+    let mut components: Vec<Component> = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                let name = String::from_utf8_lossy(e.name()).to_string();
+                match name.as_str() {
+                    "comp" => components.push(Component {
+                        designator: attribute(&reader, e, b"ref"),
+                        ..Default::default()
+                    }),
+                    "field" => field = attribute(&reader, e, b"name"),
+                    _ => (),
+                }
+                path.push(name);
+            }
+            Ok(Event::Empty(ref e)) => {
+                let comp = match components.last_mut() {
+                    Some(c) if path.iter().any(|m| m == "comp") => c,
+                    _ => continue,
+                };
+                match e.name() {
+                    b"libsource" if comp.description.is_empty() => {
+                        comp.description = attribute(&reader, e, b"description");
+                    }
+                    b"field" => {
+                        comp.fields
+                            .push((attribute(&reader, e, b"name"), String::new()));
+                    }
+                    _ => (),
+                }
+            }
+            Ok(Event::Text(ref e)) => {
+                let text = match e.unescape_and_decode(&reader) {
+                    Ok(t) => t,
+                    Err(error) => return Err(format!("Invalid KiCad xml: {:?}", error)),
+                };
+                let comp = match components.last_mut() {
+                    Some(c) if path.iter().any(|m| m == "comp") => c,
+                    _ => continue,
+                };
+                match path.last().map(|m| m.as_str()) {
+                    Some("value") => comp.value = text,
+                    Some("footprint") => comp.footprint = text,
+                    Some("description") => comp.description = text,
+                    Some("datasheet") => comp.datasheet = text,
+                    Some("field") => {
+                        comp.fields.push((field.clone(), text));
+                    }
+                    _ => (),
+                }
+            }
+            Ok(Event::End(_)) => {
+                path.pop();
+            }
+            Ok(Event::Eof) => break,
+            Err(error) => {
+                return Err(format!(
+                    "Invalid KiCad xml at {}: {:?}",
+                    reader.buffer_position(),
+                    error
+                ))
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    // Custom fields in order of appearance, known columns keep their name
+    let mut fields: Vec<String> = Vec::new();
+    for c in components.iter() {
+        for (name, _) in c.fields.iter() {
+            if !fields.contains(name) {
+                fields.push(name.clone());
+            }
+        }
+    }
+    let label = |name: &str| match DataParser::match_header(name, 0) {
+        Some(h) if h.key != Header::Designator && h.key != Header::Comment => name.to_string(),
+        _ => format!("NOTE {}", name),
+    };
+
+    let mut header = [
+        "Designator",
+        "Comment",
+        "Footprint",
+        "Description",
+        "NOTE datasheet",
+    ]
+    .iter()
+    .map(|m| m.to_string())
+    .collect::<Vec<_>>();
+    header.extend(fields.iter().map(|m| label(m)));
+
+    let mut rows = vec![header];
+    for c in components {
+        let datasheet = match c.datasheet.as_str() {
+            "~" => String::new(),
+            _ => c.datasheet,
+        };
+        let mut row = vec![c.designator, c.value, c.footprint, c.description, datasheet];
+        let values = c.fields;
+        row.extend(
+            fields
+                .iter()
+                .map(|f| match values.iter().find(|m| m.0 == *f) {
+                    Some((_, v)) => v.clone(),
+                    None => String::new(),
+                }),
+        );
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn attribute(reader: &Reader<&[u8]>, e: &BytesStart, name: &[u8]) -> String {
+    e.attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key == name)
+        .and_then(|a| a.unescape_and_decode_value(reader).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::Category;
+    use crate::load::Load;
+
+    #[test]
+    fn test_kicad_xml() {
+        let rows = xml_rows(&std::fs::read("test_data/kicad0.xml").unwrap()).unwrap();
+        assert_eq!(
+            rows[0],
+            vec![
+                "Designator",
+                "Comment",
+                "Footprint",
+                "Description",
+                "NOTE datasheet",
+                "Manufacturer",
+                "MPN",
+                "NOTE Farnell"
+            ]
+        );
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[4][3], "Digital temperature sensor & thermal watchdog");
+        assert_eq!(rows[4][6], "LM75BD,118");
+
+        let data = DataParser::new(Load::new("test_data/kicad0.xml").unwrap()).unwrap();
+        assert_eq!(data.items().len(), 3);
+        let r = &data.items()[0];
+        assert_eq!(r.category, Category::Resistors);
+        assert_eq!(r.designator, vec!["R1", "R2"]);
+        assert_eq!(r.part_numbers[0].mpn, "RC0603FR-0710KL");
+        assert_eq!(r.extra[1].value, "9330399");
+        assert_eq!(r.description, "Resistor");
+    }
+}
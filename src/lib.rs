@@ -0,0 +1,68 @@
+//! Parse, merge and format Bill Of Materials.
+//!
+//! A BOM spreadsheet is read with [`Load`], its header and rows are turned into
+//! merged [`Item`]s by [`DataParser`] and written out by [`OutJobXlsx`]:
+//!
+//! ```no_run
+//! use bomerge::{DataParser, Load, OutJobXlsx};
+//!
+//! # fn main() -> Result<(), String> {
+//! let data = DataParser::new(Load::new("bom.xlsx")?)?;
+//! for item in data.items() {
+//!     println!("{} {}", item.quantity, item.designator.join(", "));
+//! }
+//! OutJobXlsx::new("merged_bom").write(data.headers(), data.items(), data.categories())?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Reading and parsing a BOM, loading the settings files, like templates,
+//! projects and rules, and writing the outputs return an error message when
+//! they fail, so the crate can be used on uploaded or streamed content.
+//!
+//! Progress is reported through the `log` facade, parsing details at debug
+//! and trace level. The `bomerge` binary is a thin command line front end to
+//! this crate.
+
+/// Changes between two BOMs, part by part.
+pub mod diff;
+/// CSV, JSON, Markdown and plain text writers, for files or stdout.
+pub mod export;
+/// Self-contained HTML report.
+pub mod html;
+/// BOM header detection, row parsing and merge.
+pub mod items;
+/// KiCad XML netlist reader.
+pub mod kicad;
+/// Spreadsheet, CSV and KiCad reader.
+pub mod load;
+/// Project information for the cover sheet.
+pub mod metadata;
+/// OpenDocument spreadsheet writer.
+pub mod ods;
+/// xlsx writer.
+pub mod outjob;
+/// Pick-and-place files and cross-check with the BOM.
+pub mod pnp;
+/// Project file with inputs, outputs and options of a merge.
+pub mod project;
+/// Validation rules over the parsed items.
+pub mod rules;
+/// Counts by category, side and input file.
+pub mod stats;
+/// Output layout: columns, formats and sorting.
+pub mod template;
+/// Component value and designator helpers.
+pub mod utils;
+
+pub use items::{
+    Category, DataParser, Header, HeaderMap, Item, MergeKey, Mount, ParseOptions, Side, SortKey,
+};
+pub use load::{InputFormat, Load, RowSource};
+pub use metadata::Metadata;
+pub use ods::OutJobOds;
+pub use outjob::OutJobXlsx;
+pub use pnp::PickPlace;
+pub use project::{Project, ProjectInput, ProjectOutput};
+pub use stats::StatsReport;
+pub use template::Template;
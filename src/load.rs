@@ -0,0 +1,240 @@
+use calamine::{DataType, Ods, Range, Reader, Xls, Xlsx};
+use log::info;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+
+use super::kicad;
+
+/// Anything which yields the cells of a BOM sheet, row by row.
+pub trait RowSource {
+    fn rows(&mut self) -> Vec<Vec<String>>;
+}
+
+/// Rows already in memory, like a parsed CSV or a test table.
+impl RowSource for Vec<Vec<String>> {
+    fn rows(&mut self) -> Vec<Vec<String>> {
+        std::mem::take(self)
+    }
+}
+
+/// Literal rows of a test table, made owned for `RowSource`.
+#[cfg(test)]
+pub(crate) fn to_rows(data: &[&[&str]]) -> Vec<Vec<String>> {
+    data.iter()
+        .map(|r| r.iter().map(|c| c.to_string()).collect())
+        .collect()
+}
+
+/// Output path, without extension, of a test, unique to the test run.
+#[cfg(test)]
+pub(crate) fn temp_path(test: &str) -> String {
+    let name = format!("bomerge_{}_{}", test, std::process::id());
+    std::env::temp_dir()
+        .join(name)
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Format of a BOM, from the file extension or the content when not known.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InputFormat {
+    Xlsx,
+    Xls,
+    Ods,
+    Csv,
+    /// KiCad eeschema XML netlist or intermediate BOM.
+    KicadXml,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<InputFormat, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "xlsx" | "xlsm" => Ok(InputFormat::Xlsx),
+            "xls" => Ok(InputFormat::Xls),
+            "ods" => Ok(InputFormat::Ods),
+            "csv" | "txt" => Ok(InputFormat::Csv),
+            "xml" | "kicad" => Ok(InputFormat::KicadXml),
+            _ => Err(format!("Invalid input format [{}]", s)),
+        }
+    }
+}
+
+impl InputFormat {
+    /// Guess the format from the first bytes: zip for xlsx and ods, OLE for xls.
+    pub fn detect(content: &[u8]) -> InputFormat {
+        if content.starts_with(b"PK\x03\x04") {
+            // ods keep their mimetype uncompressed at the start of the archive
+            let head = &content[..content.len().min(100)];
+            if head.windows(24).any(|m| m == b"application/vnd.oasis.op") {
+                return InputFormat::Ods;
+            }
+            return InputFormat::Xlsx;
+        }
+        if content.starts_with(&[0xD0, 0xCF, 0x11, 0xE0]) {
+            return InputFormat::Xls;
+        }
+        if kicad::is_kicad_xml(content) {
+            return InputFormat::KicadXml;
+        }
+        InputFormat::Csv
+    }
+}
+
+/// Cells of the sheet with the BOM, read from a file or from memory.
+pub struct Load {
+    sheet_name: String,
+    rows: Vec<Vec<String>>,
+}
+
+impl Load {
+    /// Load the first sheet of a BOM file, the format from its extension or
+    /// its content. Errors when the file can not be read or parsed.
+    pub fn new(filename: &str) -> Result<Load, String> {
+        info!("Parse: {}", filename);
+        let content = match fs::read(filename) {
+            Ok(c) => c,
+            Err(error) => return Err(format!("Unable to read {}: {}", filename, error)),
+        };
+        let format = filename
+            .rsplit('.')
+            .next()
+            .and_then(|ext| ext.parse().ok())
+            .unwrap_or_else(|| InputFormat::detect(&content));
+        Self::from_bytes(content, Some(format))
+    }
+
+    /// Load a BOM read from stdin or any other stream.
+    pub fn from_reader<R: Read>(
+        mut reader: R,
+        format: Option<InputFormat>,
+    ) -> Result<Load, String> {
+        let mut content = Vec::new();
+        if let Err(error) = reader.read_to_end(&mut content) {
+            return Err(format!("Unable to read input: {}", error));
+        }
+        Self::from_bytes(content, format)
+    }
+
+    /// Load a BOM from memory, the format guessed from the content when not given.
+    pub fn from_bytes(content: Vec<u8>, format: Option<InputFormat>) -> Result<Load, String> {
+        Self::parse(content, format, None)
+    }
+
+    /// Same as `from_bytes`, reading the named worksheet in place of the first one.
+    pub fn from_sheet(
+        content: Vec<u8>,
+        format: Option<InputFormat>,
+        sheet: &str,
+    ) -> Result<Load, String> {
+        Self::parse(content, format, Some(sheet))
+    }
+
+    fn parse(
+        content: Vec<u8>,
+        format: Option<InputFormat>,
+        sheet: Option<&str>,
+    ) -> Result<Load, String> {
+        let format = format.unwrap_or_else(|| InputFormat::detect(&content));
+        let (sheet_name, rows) = match format {
+            InputFormat::Xlsx => Self::sheet::<Xlsx<_>>(content, sheet)?,
+            InputFormat::Xls => Self::sheet::<Xls<_>>(content, sheet)?,
+            InputFormat::Ods => Self::sheet::<Ods<_>>(content, sheet)?,
+            InputFormat::Csv => (String::from("csv"), Self::csv_rows(&content)?),
+            InputFormat::KicadXml => (String::from("kicad"), kicad::xml_rows(&content)?),
+        };
+
+        info!("Sheets: {}", sheet_name);
+        Ok(Load { sheet_name, rows })
+    }
+
+    fn sheet<R>(content: Vec<u8>, sheet: Option<&str>) -> Result<(String, Vec<Vec<String>>), String>
+    where
+        R: Reader<RS = Cursor<Vec<u8>>>,
+        R::Error: std::fmt::Debug,
+    {
+        let mut workbook = match R::new(Cursor::new(content)) {
+            Ok(wk) => wk,
+            Err(error) => return Err(format!("Invalid workbook: {:?}", error)),
+        };
+        /* Search headers in source files */
+        let sheet_name = match sheet {
+            Some(s) => String::from(s),
+            None => match workbook.sheet_names().first() {
+                Some(s) => s.clone(),
+                None => return Err(String::from("No sheets in the workbook")),
+            },
+        };
+        match workbook.worksheet_range(sheet_name.as_str()) {
+            Some(Ok(range)) => Ok((sheet_name, Self::range_rows(&range))),
+            Some(Err(error)) => Err(format!("Invalid sheet [{}]: {:?}", sheet_name, error)),
+            None => Err(format!("No sheet named [{}]", sheet_name)),
+        }
+    }
+
+    fn range_rows(range: &Range<DataType>) -> Vec<Vec<String>> {
+        let mut data: Vec<Vec<String>> = Vec::new();
+        let (rw, cl) = range.get_size();
+        // Used range may not start at A1, pad it to keep sheet row and column numbers
+        let (start_row, start_col) = range.start().unwrap_or((0, 0));
+        for _ in 0..start_row {
+            data.push(Vec::new());
+        }
+        for row in 0..rw {
+            let mut element: Vec<String> = vec![String::new(); start_col as usize];
+            for column in 0..cl {
+                element.push(match range.get((row, column)) {
+                    Some(DataType::String(s)) => s.clone(),
+                    Some(DataType::Float(f)) => f.to_string(),
+                    Some(DataType::Int(i)) => i.to_string(),
+                    Some(DataType::Bool(b)) => b.to_string(),
+                    _ => String::new(),
+                });
+            }
+            data.push(element);
+        }
+        data
+    }
+
+    /// Comma or semicolon separated rows, as exported by the EDA tools.
+    fn csv_rows(content: &[u8]) -> Result<Vec<Vec<String>>, String> {
+        let text = String::from_utf8_lossy(content);
+        let text = text.trim_start_matches('\u{feff}');
+        let first = text.lines().next().unwrap_or_default();
+        let delimiter = if first.matches(';').count() > first.matches(',').count() {
+            b';'
+        } else {
+            b','
+        };
+        let mut rd = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(delimiter)
+            .from_reader(text.as_bytes());
+        rd.records()
+            .map(|r| match r {
+                Ok(record) => Ok(record.iter().map(String::from).collect()),
+                Err(error) => Err(format!("Invalid csv: {}", error)),
+            })
+            .collect()
+    }
+
+    /// Name of the sheet read, "csv" or "kicad" for those formats.
+    pub fn sheet_name(&self) -> &str {
+        &self.sheet_name
+    }
+
+    /// Copy of the rows, as strings.
+    pub fn read(&mut self) -> Vec<Vec<String>> {
+        self.rows.clone()
+    }
+}
+
+impl RowSource for Load {
+    fn rows(&mut self) -> Vec<Vec<String>> {
+        std::mem::take(&mut self.rows)
+    }
+}
@@ -1,43 +1,893 @@
-use clap::{App, Arg};
-mod lib;
-use lib::items::Category;
-use lib::items::DataParser;
-use lib::load::Load;
-use lib::outjob::OutJobXlsx;
-use lib::ASCII_LOGO;
+use bomerge::diff::compare;
+use bomerge::export::{write_text, TextFormat};
+use bomerge::html::write_html;
+use bomerge::pnp::PnpIssue;
+use bomerge::rules::{RuleSet, RulesConfig, Severity, Violation};
+use bomerge::{
+    Category, DataParser, Load, MergeKey, OutJobOds, OutJobXlsx, ParseOptions, PickPlace, Project,
+    ProjectInput, ProjectOutput, StatsReport, Template,
+};
+use clap::{App, AppSettings, Arg, ArgMatches, ErrorKind, SubCommand};
+use log::{error, info, warn, Level, LevelFilter, Log, Metadata as LogMetadata, Record};
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the inputs are checked for changes in watch mode.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Banner of --version and of interactive runs.
+const ASCII_LOGO: &str = r#"
+
+███╗   ███╗███████╗██████╗  ██████╗ ███████╗██████╗  ██████╗ ███╗   ███╗
+████╗ ████║██╔════╝██╔══██╗██╔════╝ ██╔════╝██╔══██╗██╔═══██╗████╗ ████║
+██╔████╔██║█████╗  ██████╔╝██║  ███╗█████╗  ██████╔╝██║   ██║██╔████╔██║
+██║╚██╔╝██║██╔══╝  ██╔══██╗██║   ██║██╔══╝  ██╔══██╗██║   ██║██║╚██╔╝██║
+██║ ╚═╝ ██║███████╗██║  ██║╚██████╔╝███████╗██████╔╝╚██████╔╝██║ ╚═╝ ██║
+╚═╝     ╚═╝╚══════╝╚═╝  ╚═╝ ╚═════╝ ╚══════╝╚═════╝  ╚═════╝ ╚═╝     ╚═╝
+"#;
+
+/// Log on stderr, as plain text or as a JSON object for each event.
+struct Logger {
+    json: bool,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if self.json {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |d| d.as_secs_f64());
+            let event = serde_json::json!({
+                "time": time,
+                "level": record.level().to_string(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            eprintln!("{}", event);
+        } else if record.level() <= Level::Warn {
+            eprintln!(
+                "{}: {}",
+                record.level().to_string().to_lowercase(),
+                record.args()
+            );
+        } else {
+            eprintln!("{}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn bom_files(help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name("BOMFile").help(help).min_values(1)
+}
+
+fn pnp_arg() -> Arg<'static, 'static> {
+    Arg::with_name("pnp")
+        .long("pnp")
+        .help("Pick and place file to cross-check with the BOM")
+        .takes_value(true)
+}
+
+fn stats_format_arg() -> Arg<'static, 'static> {
+    Arg::with_name("stats_format")
+        .long("stats-format")
+        .help("Stats printed at the end: a table or JSON")
+        .takes_value(true)
+        .possible_values(&["table", "json"])
+        .default_value("table")
+}
+
+/// Output file, format and template of the merge and convert commands.
+fn output_args(required: bool) -> Vec<Arg<'static, 'static>> {
+    vec![
+        Arg::with_name("output")
+            .long("output")
+            .short("o")
+            .help("Output file, - to write csv, json, html, md or txt to stdout")
+            .takes_value(true)
+            .required(required),
+        Arg::with_name("output_format")
+            .long("output-format")
+            .alias("format")
+            .help("Output format, from the output extension when not given")
+            .takes_value(true)
+            .possible_values(&["xlsx", "ods", "csv", "json", "html", "md", "txt"]),
+        Arg::with_name("template")
+            .long("template")
+            .short("t")
+            .help("Output template with column order, labels and formats")
+            .takes_value(true),
+    ]
+}
 
 fn main() {
+    let long_version = format!("0.1.0\n{}", ASCII_LOGO);
     let matches = App::new("Rust MergeBom")
         .version("0.1.0")
+        .long_version(long_version.as_str())
         .author("Daniele Basile <asterix24@gmail.com>")
         .about("Pretty merger and formatter Bill Of Materials.")
+        .setting(AppSettings::ArgRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("project")
+                .long("project")
+                .help("Project file, like bomerge.toml, with inputs, outputs and options, merged when no command is given")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("input_format")
+                .long("input-format")
+                .help("Format of the BOM, guessed from extension or content when not given")
+                .takes_value(true)
+                .possible_values(&["xlsx", "xls", "ods", "csv", "kicad"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .long("quiet")
+                .short("q")
+                .help("Only print errors")
+                .conflicts_with("verbose")
+                .global(true),
+        )
         .arg(
-            Arg::with_name("BOMFile")
-                .help("BOM to Merge")
-                .required(true)
-                .min_values(1),
+            Arg::with_name("verbose")
+                .short("v")
+                .multiple(true)
+                .help("Print parsing details, -vv for every row")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("log_format")
+                .long("log-format")
+                .help("Log messages as text or as JSON lines")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("merge_key")
+                .long("merge-key")
+                .help("Fields used to merge rows: component value or manufacturer part number")
+                .takes_value(true)
+                .possible_values(&["value", "mpn"])
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("undesignated_category")
+                .long("undesignated-category")
+                .help("Category for rows without designator, like screws, labels or PCB, default Mechanicals")
+                .takes_value(true)
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Merge the BOMs in one and write it to the outputs")
+                .arg(bom_files("BOM to Merge, - to read it from stdin"))
+                .args(&output_args(false))
+                .arg(
+                    Arg::with_name("category_sheets")
+                        .long("category-sheets")
+                        .help("Add a worksheet for each category after the combined one"),
+                )
+                .arg(
+                    Arg::with_name("side_sheets")
+                        .long("side-sheets")
+                        .help("Add a worksheet for each board side, to quote each assembly pass"),
+                )
+                .arg(
+                    Arg::with_name("cover")
+                        .long("cover")
+                        .help("Add a summary sheet with project information before the BOM"),
+                )
+                .arg(
+                    Arg::with_name("project_name")
+                        .long("project-name")
+                        .help("Project name")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("revision")
+                        .long("revision")
+                        .help("Board revision")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("author")
+                        .long("author")
+                        .help("BOM author")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("date")
+                        .long("date")
+                        .help("BOM date, today when not given")
+                        .takes_value(true),
+                )
+                .arg(pnp_arg())
+                .arg(
+                    Arg::with_name("pnp_out")
+                        .long("pnp-out")
+                        .help("Write the pick and place file with only the fitted parts")
+                        .takes_value(true)
+                        .requires("pnp"),
+                )
+                .arg(stats_format_arg())
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .help("Keep running and regenerate the output when an input file changes"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Parse, validate and cross-check the BOMs, exit with 1 on errors")
+                .arg(bom_files("BOM to check, - to read it from stdin"))
+                .arg(pnp_arg())
+                .arg(
+                    Arg::with_name("rules")
+                        .long("rules")
+                        .help("Rules file to enable, disable or set the severity of the checks")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("report_format")
+                        .long("report-format")
+                        .help("Rule violations printed as text or JSON")
+                        .takes_value(true)
+                        .possible_values(&["text", "json"])
+                        .default_value("text"),
+                ),
         )
-        .get_matches();
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Print the statistics of the BOMs")
+                .arg(bom_files("BOM to count, - to read it from stdin"))
+                .arg(stats_format_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Write a BOM in another format, without merging its rows")
+                .arg(
+                    Arg::with_name("BOMFile")
+                        .help("BOM to convert, - to read it from stdin")
+                        .required(true),
+                )
+                .args(&output_args(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Print the parts added, removed or changed, exit with 1 when they differ")
+                .arg(
+                    Arg::with_name("BOMFile")
+                        .help("Old and new BOM")
+                        .required(true)
+                        .number_of_values(2),
+                ),
+        )
+        .get_matches_safe()
+        .unwrap_or_else(|e| match e.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+            // Usage errors exit with 2 as the other input errors
+            _ => {
+                eprintln!("{}", e.message);
+                process::exit(2);
+            }
+        });
+
+    let (command, matches) = match matches.subcommand() {
+        (name, Some(m)) => (name, m),
+        _ if matches.is_present("project") => ("merge", &matches),
+        _ => {
+            eprintln!(
+                "error: a command or --project is required\n\n{}",
+                matches.usage()
+            );
+            process::exit(2);
+        }
+    };
+
+    let json = matches.value_of("log_format") == Some("json");
+    let level = match matches.occurrences_of("verbose") {
+        _ if matches.is_present("quiet") => LevelFilter::Error,
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    log::set_logger(Box::leak(Box::new(Logger { json }))).unwrap();
+    log::set_max_level(level);
+    if !json && level >= LevelFilter::Info && io::stderr().is_terminal() {
+        eprintln!("{}", ASCII_LOGO);
+    }
+
+    match command {
+        "merge" => merge(matches),
+        "check" => check(matches),
+        "stats" => stats(matches),
+        "convert" => convert(matches),
+        "diff" => diff(matches),
+        _ => unreachable!(),
+    }
+}
 
-    println!("{}", ASCII_LOGO);
+/// Report an error in the command line, the settings or the inputs and exit
+/// with 2, apart from the 1 of failed checks.
+fn fail(message: &str) -> ! {
+    error!("{}", message);
+    process::exit(2);
+}
 
-    let bom = matches.values_of("BOMFile").unwrap();
-    for i in bom {
-        let ld: Load = Load::new(i);
-        let data: DataParser = DataParser::new(ld);
+fn merge(matches: &ArgMatches) {
+    let project = project_from_args(matches).unwrap_or_else(|e| fail(&e));
+    if matches.is_present("watch") && project.input.iter().any(|m| m.path == "-") {
+        fail("stdin can not be watched, give the BOM files");
+    }
 
+    let reports = run(&project, true, None)
+        .unwrap_or_else(|e| fail(&e))
+        .reports;
+    let text = stats_text(&project, &reports).unwrap_or_else(|e| fail(&e));
+    print_report(&project, &text);
+
+    if matches.is_present("watch") {
+        watch(matches, &project, reports);
+    }
+}
+
+fn check(matches: &ArgMatches) {
+    let project = read_only(project_from_args(matches).unwrap_or_else(|e| fail(&e)));
+    let mut config = match project.rules.as_deref() {
+        Some(f) => RulesConfig::new(f).unwrap_or_else(|e| fail(&e)),
+        None => RulesConfig::default(),
+    };
+    config.categories = categories(&project).unwrap_or_else(|e| fail(&e));
+    let rules = RuleSet::with_config(&config).unwrap_or_else(|e| fail(&e));
+    let run = run(&project, true, Some(&rules)).unwrap_or_else(|e| fail(&e));
+
+    if matches.value_of("report_format") == Some("json") {
+        let list = run
+            .violations
+            .iter()
+            .map(|(source, v)| {
+                let mut obj = serde_json::json!({ "source": source });
+                if let (Some(o), serde_json::Value::Object(v)) =
+                    (obj.as_object_mut(), serde_json::to_value(v).unwrap())
+                {
+                    o.extend(v);
+                }
+                obj
+            })
+            .collect::<Vec<_>>();
+        println!("{}", serde_json::to_string_pretty(&list).unwrap());
+    } else {
+        for (source, v) in run.violations.iter() {
+            println!("{}: {}", source, v);
+        }
+    }
+
+    let count = |s: Severity| run.violations.iter().filter(|m| m.1.severity == s).count();
+    let errors = count(Severity::Error);
+    info!(
+        "{} errors, {} warnings, {} info, {} other issues",
+        errors,
+        count(Severity::Warning),
+        count(Severity::Info),
+        run.issues
+    );
+    if errors > 0 || run.issues > 0 {
+        error!("Check failed");
+        process::exit(1);
+    }
+}
+
+fn stats(matches: &ArgMatches) {
+    let project = read_only(project_from_args(matches).unwrap_or_else(|e| fail(&e)));
+    let reports = run(&project, true, None)
+        .unwrap_or_else(|e| fail(&e))
+        .reports;
+    print!(
+        "{}",
+        stats_text(&project, &reports).unwrap_or_else(|e| fail(&e))
+    );
+}
+
+/// The project without the outputs, for the commands which only read the inputs.
+fn read_only(mut project: Project) -> Project {
+    project.output.clear();
+    project.pnp_out = None;
+    project
+}
+
+fn convert(matches: &ArgMatches) {
+    let project = project_from_args(matches).unwrap_or_else(|e| fail(&e));
+    run(&project, false, None).unwrap_or_else(|e| fail(&e));
+}
+
+fn diff(matches: &ArgMatches) {
+    let project = project_from_args(matches).unwrap_or_else(|e| fail(&e));
+    let parse = |input| parse_options(&project, input).and_then(|o| parse_input(input, &o));
+    let (_, old) = parse(&project.input[0]).unwrap_or_else(|e| fail(&e));
+    let (_, new) = parse(&project.input[1]).unwrap_or_else(|e| fail(&e));
+    let changes = compare(old.items(), new.items());
+    for c in changes.iter() {
+        println!("{}", c);
+    }
+    if !changes.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// The --project file, if any, with the command line flags on top.
+fn project_from_args(matches: &ArgMatches) -> Result<Project, String> {
+    let mut project = match matches.value_of("project") {
+        Some(f) => Project::new(f)?,
+        None => Project::default(),
+    };
+
+    if let Some(bom) = matches.values_of("BOMFile") {
+        project.input = bom.map(ProjectInput::new).collect();
+    }
+    if project.input.is_empty() {
+        return Err(String::from(
+            "No BOM given, give the BOM files or a project with inputs",
+        ));
+    }
+    if let Some(f) = matches.value_of("input_format") {
+        for i in project.input.iter_mut() {
+            i.format = Some(f.to_string());
+        }
+    }
+
+    if let Some(path) = matches.value_of("output") {
+        project.output = vec![ProjectOutput {
+            path: path.to_string(),
+            format: None,
+        }];
+    } else if project.output.is_empty() {
+        project.output = vec![ProjectOutput {
+            path: String::from("merged_bom.xlsx"),
+            format: None,
+        }];
+    }
+    if let Some(f) = matches.value_of("output_format") {
+        for o in project.output.iter_mut() {
+            o.format = Some(f.to_string());
+        }
+    }
+    for (name, field) in [
+        ("merge_key", &mut project.merge_key),
+        ("undesignated_category", &mut project.undesignated_category),
+        ("template", &mut project.template),
+        ("pnp", &mut project.pnp),
+        ("pnp_out", &mut project.pnp_out),
+        ("rules", &mut project.rules),
+    ] {
+        if let Some(v) = matches.value_of(name) {
+            *field = Some(v.to_string());
+        }
+    }
+    // The default value takes the place of the project one only when given
+    if matches.occurrences_of("stats_format") > 0 || project.stats_format.is_none() {
+        project.stats_format = matches.value_of("stats_format").map(String::from);
+    }
+    project.cover |= matches.is_present("cover");
+    project.category_sheets |= matches.is_present("category_sheets");
+    project.side_sheets |= matches.is_present("side_sheets");
+
+    if let Some(v) = matches.value_of("project_name") {
+        project.metadata.project = v.to_string();
+    }
+    if let Some(v) = matches.value_of("revision") {
+        project.metadata.revision = v.to_string();
+    }
+    if let Some(v) = matches.value_of("author") {
+        project.metadata.author = v.to_string();
+    }
+    if let Some(v) = matches.value_of("date") {
+        project.metadata.date = v.to_string();
+    }
+    for output in project.output.iter() {
+        let format = output_format(output)?;
+        if output.path == "-" && (format == "xlsx" || format == "ods") {
+            return Err(format!(
+                "{} can not be written to stdout, use a text format",
+                format
+            ));
+        }
+    }
+    Ok(project)
+}
+
+/// Stats of each variant, a JSON array when there is more than one.
+fn stats_text(project: &Project, reports: &[StatsReport]) -> Result<String, String> {
+    Ok(match (project.stats_format.as_deref(), reports) {
+        (Some("json"), [report]) => format!("{}\n", report.to_json()?),
+        (Some("json"), _) => format!(
+            "{}\n",
+            serde_json::to_string_pretty(reports).map_err(|e| e.to_string())?
+        ),
+        _ => reports
+            .iter()
+            .map(|m| m.table())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    })
+}
+
+/// Counts changed since the previous run, for each variant.
+fn stats_diff(reports: &[StatsReport], previous: &[StatsReport]) -> String {
+    let mut out = String::new();
+    for report in reports {
+        let before = previous
+            .iter()
+            .find(|m| m.variant == report.variant)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(v) = &report.variant {
+            out.push_str(&format!("Variant {}\n", v));
+        }
+        out.push_str(&report.diff(&before));
+    }
+    out
+}
+
+/// Print the stats, keeping stdout clean when it carries the BOM.
+fn print_report(project: &Project, text: &str) {
+    if !project.output.iter().any(|m| m.path == "-") {
+        print!("{}", text);
+    } else if log::log_enabled!(Level::Info) {
+        eprint!("{}", text);
+    }
+}
+
+/// Format of an output, from its extension when not given.
+fn output_format(output: &ProjectOutput) -> Result<&str, String> {
+    let path = output.path.as_str();
+    Ok(match output.format.as_deref() {
+        Some(f @ ("xlsx" | "ods" | "csv" | "json" | "html" | "md" | "txt")) => f,
+        Some(f) => return Err(format!("Invalid output format [{}]", f)),
+        None if path == "-" => "csv",
+        None if path.ends_with(".csv") => "csv",
+        None if path.ends_with(".json") => "json",
+        None if path.ends_with(".ods") => "ods",
+        None if path.ends_with(".html") => "html",
+        None if path.ends_with(".md") => "md",
+        None if path.ends_with(".txt") => "txt",
+        None => "xlsx",
+    })
+}
+
+/// Category overrides of the project, by designator prefix.
+fn categories(project: &Project) -> Result<Vec<(String, Category)>, String> {
+    project
+        .categories
+        .iter()
+        .map(|(prefix, c)| Ok((prefix.clone(), c.parse()?)))
+        .collect()
+}
+
+fn parse_options(project: &Project, input: &ProjectInput) -> Result<ParseOptions, String> {
+    let merge_key = match project.merge_key.as_deref() {
+        Some("mpn") => MergeKey::PartNumber,
+        Some("value") | None => MergeKey::Value,
+        Some(k) => return Err(format!("Invalid merge key [{}]", k)),
+    };
+    let undesignated: Category = project
+        .undesignated_category
+        .as_deref()
+        .unwrap_or("Mechanicals")
+        .parse()?;
+    Ok(ParseOptions {
+        merge_key,
+        undesignated,
+        categories: categories(project)?,
+        multiplier: input.multiplier,
+        merge: true,
+    })
+}
+
+/// Read and parse an input BOM, the content is kept for its hash.
+fn parse_input(
+    input: &ProjectInput,
+    options: &ParseOptions,
+) -> Result<(Vec<u8>, DataParser), String> {
+    let i = input.path.as_str();
+    let content = if i == "-" {
+        let mut buf = Vec::new();
+        if let Err(error) = io::stdin().read_to_end(&mut buf) {
+            return Err(format!("Unable to read stdin: {}", error));
+        }
+        buf
+    } else {
+        match std::fs::read(i) {
+            Ok(c) => c,
+            Err(error) => return Err(format!("Unable to read {}: {}", i, error)),
+        }
+    };
+    let format = match input.format.as_deref() {
+        Some(f) => Some(f.parse()?),
+        None => i.rsplit('.').next().and_then(|e| e.parse().ok()),
+    };
+    info!("Parse: {}", i);
+    let ld = match input.sheet.as_deref() {
+        Some(sheet) => Load::from_sheet(content.clone(), format, sheet),
+        None => Load::from_bytes(content.clone(), format),
+    };
+    let data = match ld.and_then(|ld| DataParser::with_options(ld, options)) {
+        Ok(data) => data,
+        Err(error) => return Err(format!("{}: {}", i, error)),
+    };
+    info!("Header row: {}", data.header_row() + 1);
+    for m in data.quantity_mismatches() {
+        warn!(
+            "Quantity mismatch at row {}: declared {}, found {} designators [{}]",
+            m.row + 1,
+            m.quantity,
+            m.designator.len(),
+            m.designator.join(", ")
+        );
+    }
+    Ok((content, data))
+}
+
+/// Outcome of a run over the project inputs.
+struct Run {
+    /// Stats of the merged BOM of each variant.
+    reports: Vec<StatsReport>,
+    /// Quantity mismatches and pick and place issues found.
+    issues: usize,
+    /// Rule violations, with the input they are found in.
+    violations: Vec<(String, Violation)>,
+}
+
+/// Path with the variant added to the file name, before the extension.
+fn variant_path(path: &str, variant: Option<&str>) -> String {
+    let p = Path::new(path);
+    match (variant, p.extension()) {
+        (None, _) => path.to_string(),
+        (Some(v), Some(ext)) => format!(
+            "{}-{}.{}",
+            p.with_extension("").to_string_lossy(),
+            v,
+            ext.to_string_lossy()
+        ),
+        (Some(v), None) => format!("{}-{}", path, v),
+    }
+}
+
+/// Load the input BOMs of the project, merge them in one and write it to
+/// each output. Inputs with a variant are merged and written apart, with the
+/// variant in the output names. With `merge` false the rows are written as
+/// they are in the source, with `rules` the items are validated too.
+fn run(project: &Project, merge: bool, rules: Option<&RuleSet>) -> Result<Run, String> {
+    let mut template = match project.template.as_deref() {
+        Some(t) => Template::new(t)?,
+        None => Template::default(),
+    };
+    if project.cover {
+        template.cover.enabled = true;
+    }
+    if project.category_sheets {
+        template.category_sheets = true;
+    }
+    if project.side_sheets {
+        template.side_sheets = true;
+    }
+
+    // Inputs of each variant, the ones without it first
+    let mut variants: Vec<(Option<&str>, Vec<&ProjectInput>)> = Vec::new();
+    for input in project.input.iter() {
+        let variant = input.variant.as_deref();
+        match variants.iter_mut().find(|m| m.0 == variant) {
+            Some((_, inputs)) => inputs.push(input),
+            None => variants.push((variant, vec![input])),
+        }
+    }
+    variants.sort_by_key(|m| m.0.is_some());
+
+    let pnp = project.pnp.as_deref().map(PickPlace::new).transpose()?;
+    let mut reports = Vec::new();
+    let mut issues = 0;
+    let mut violations = Vec::new();
+    for (variant, inputs) in variants {
+        let mut bom_metadata = project.metadata.clone();
+        if let Some(v) = variant {
+            bom_metadata.variant = v.to_string();
+        }
+        let mut boms = Vec::new();
+        let mut sources = StatsReport::default();
+        for input in inputs {
+            let i = input.path.as_str();
+            let options = ParseOptions {
+                merge,
+                ..parse_options(project, input)?
+            };
+            let (content, data) = parse_input(input, &options)?;
+            issues += data.quantity_mismatches().len();
+            if let Some(rules) = rules {
+                for v in rules.check(data.items()) {
+                    violations.push((input.path.clone(), v));
+                }
+            }
+            sources.add_source(i, data.items());
+            bom_metadata.add_input_data(i, &content);
+            boms.push(data);
+        }
+        let data = DataParser::combine(boms, merge);
         let c: Vec<Category> = data.categories();
-        for x in data.stats() {
-            println!("->\t{:?} {}", x.label, x.value);
-        }
-        //dump(&v);
-        // for i in c.iter() {
-        //     println!("-> {:?}", i);
-        // }
-        // for i in hdr.iter() {
-        //     println!("=> {:?}", i);
-        // }
-        let out = OutJobXlsx::new("merged_bom");
-        out.write(data.headers(), data.items(), c.clone());
+        let mut report = StatsReport::new(data.items());
+        report.variant = variant.map(String::from);
+        report.sources = sources.sources;
+        reports.push(report);
+
+        if let Some(pnp) = &pnp {
+            info!("Placements: {}", pnp.placements().len());
+            for issue in pnp.check(data.items()) {
+                issues += 1;
+                match issue {
+                    PnpIssue::MissingInPnp(d) => warn!("{} missing in pick and place", d),
+                    PnpIssue::MissingInBom(d) => warn!("{} missing in BOM", d),
+                    PnpIssue::LayerMismatch {
+                        designator,
+                        bom,
+                        pnp,
+                    } => warn!(
+                        "{} layer mismatch: {} in BOM, {} in pick and place",
+                        designator, bom, pnp
+                    ),
+                    PnpIssue::NotFitted(d) => warn!("{} not fitted but in pick and place", d),
+                }
+            }
+            if let Some(f) = project.pnp_out.as_deref() {
+                pnp.write_fitted(&variant_path(f, variant), data.items())?;
+            }
+        }
+
+        for output in project.output.iter() {
+            let output_format = output_format(output)?;
+            // Output name without extension, the writers add the one of the format
+            let path = output.path.as_str();
+            let base = match path.rsplit_once('.') {
+                Some((stem, "xlsx" | "ods" | "csv" | "json" | "html" | "md" | "txt")) => stem,
+                _ => path,
+            };
+            let base = match variant {
+                Some(v) => format!("{}-{}", base, v),
+                None => base.to_string(),
+            };
+            if output_format == "xlsx" {
+                let mut out = OutJobXlsx::with_template(&base, template.clone());
+                out.set_metadata(bom_metadata.clone());
+                out.write(data.headers(), data.items(), c.clone())?;
+            } else if output_format == "ods" {
+                let mut out = OutJobOds::with_template(&base, template.clone());
+                out.set_metadata(bom_metadata.clone());
+                out.write(data.headers(), data.items(), c.clone())?;
+            } else {
+                let out: Box<dyn Write> = if path == "-" {
+                    Box::new(io::stdout())
+                } else {
+                    let filename = format!("{}.{}", base, output_format);
+                    match File::create(&filename) {
+                        Ok(f) => Box::new(f),
+                        Err(error) => {
+                            return Err(format!("Unable to create {}: {}", filename, error))
+                        }
+                    }
+                };
+                match output_format {
+                    "html" => write_html(
+                        out,
+                        &template,
+                        &bom_metadata,
+                        data.headers(),
+                        data.items(),
+                        &c,
+                    ),
+                    f => {
+                        let text_format = match f {
+                            "json" => TextFormat::Json,
+                            "md" => TextFormat::Markdown,
+                            "txt" => TextFormat::Text,
+                            _ => TextFormat::Csv,
+                        };
+                        write_text(
+                            out,
+                            text_format,
+                            &template,
+                            data.headers(),
+                            data.items(),
+                            &c,
+                        )
+                    }
+                }?;
+            }
+        }
+    }
+    Ok(Run {
+        reports,
+        issues,
+        violations,
+    })
+}
+
+/// Files read by a run: the BOMs, the project, template and pick and place files.
+fn watched_files(matches: &ArgMatches, project: &Project) -> Vec<String> {
+    let mut files = project
+        .input
+        .iter()
+        .map(|m| m.path.clone())
+        .collect::<Vec<_>>();
+    for f in [
+        matches.value_of("project"),
+        project.template.as_deref(),
+        project.pnp.as_deref(),
+    ]
+    .iter()
+    .flatten()
+    {
+        files.push(f.to_string());
+    }
+    files
+}
+
+fn modified(files: &[String]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|f| std::fs::metadata(f).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Poll the inputs and run again when one changes, printing how the counts
+/// moved from the previous run. A failed run is reported and the watch goes on.
+fn watch(matches: &ArgMatches, project: &Project, mut previous: Vec<StatsReport>) {
+    let mut files = watched_files(matches, project);
+    let mut stamps = modified(&files);
+    info!("Watching {} files for changes, Ctrl-C to stop", files.len());
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let mut current = modified(&files);
+        if current == stamps {
+            continue;
+        }
+        // Exporters write in more steps, wait for the files to settle
+        while current != stamps {
+            stamps = current;
+            thread::sleep(WATCH_INTERVAL);
+            current = modified(&files);
+        }
+
+        info!("Inputs changed, regenerating the output");
+        // The project file is read again, it may list other inputs now
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let project = project_from_args(matches)?;
+            let reports = run(&project, true, None)?.reports;
+            Ok::<_, String>((project, reports))
+        }));
+        match result {
+            Ok(Ok((project, reports))) => {
+                print_report(&project, &stats_diff(&reports, &previous));
+                files = watched_files(matches, &project);
+                stamps = modified(&files);
+                previous = reports;
+            }
+            Ok(Err(e)) => warn!("{}, waiting for the next change", e),
+            Err(_) => warn!("Run failed, waiting for the next change"),
+        }
     }
 }
@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Input BOM listed in the cover sheet.
+#[derive(Debug, Clone)]
+pub struct InputFile {
+    pub path: String,
+    pub sha256: String,
+}
+
+/// Project information shown in the cover sheet, from the top level keys of
+/// the project file, like:
+///
+/// ```toml
+/// project = "Motor driver"
+/// revision = "B"
+/// author = "Daniele Basile"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    pub project: String,
+    pub revision: String,
+    /// Assembly variant the BOM is for, if any.
+    pub variant: String,
+    pub author: String,
+    /// Defaults to today when empty.
+    pub date: String,
+    #[serde(skip)]
+    pub inputs: Vec<InputFile>,
+}
+
+impl Metadata {
+    /// Record an input BOM together with the sha256 of its content.
+    pub fn add_input(&mut self, path: &str) -> Result<(), String> {
+        let content = match fs::read(path) {
+            Ok(c) => c,
+            Err(error) => return Err(format!("Unable to read {}: {}", path, error)),
+        };
+        self.add_input_data(path, &content);
+        Ok(())
+    }
+
+    /// Same as `add_input` for a BOM already in memory, like one read from stdin.
+    pub fn add_input_data(&mut self, path: &str, content: &[u8]) {
+        self.inputs.push(InputFile {
+            path: String::from(path),
+            sha256: format!("{:x}", Sha256::digest(content)),
+        });
+    }
+
+    /// Date of the BOM, today when not set.
+    pub fn date(&self) -> String {
+        if self.date.is_empty() {
+            today()
+        } else {
+            self.date.clone()
+        }
+    }
+}
+
+/// Current UTC date as YYYY-MM-DD.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (y, m, d) = civil_from_days((secs / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19723), (2024, 1, 1));
+    }
+}
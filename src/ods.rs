@@ -0,0 +1,357 @@
+use std::fs::File;
+use std::io::Write;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::items::{Category, HeaderMap, Item};
+use super::metadata::Metadata;
+use super::outjob::{cover_rows, side_items};
+use super::template::{auto_width, cell_value, parse_color, CellFormat, Template};
+use super::utils::escape_xml;
+
+const MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+
+/// OpenDocument spreadsheet with the same layout of `OutJobXlsx`.
+pub struct OutJobOds {
+    filename: String,
+    template: Template,
+    metadata: Metadata,
+    /// Automatic cell styles, the name is the position: ce0, ce1..
+    styles: Vec<String>,
+}
+
+/// Cell style properties for a template format.
+fn cell_style(name: &str, cell: &CellFormat) -> Result<String, String> {
+    let mut table = String::new();
+    let mut text = String::new();
+    if cell.text_wrap {
+        table.push_str(r#" fo:wrap-option="wrap""#);
+    }
+    if cell.border {
+        table.push_str(r#" fo:border="0.06pt solid #000000""#);
+    }
+    if let Some(c) = &cell.bg_color {
+        table.push_str(&format!(r#" fo:background-color="{}""#, parse_color(c)?));
+    }
+    if cell.bold {
+        text.push_str(r#" fo:font-weight="bold""#);
+    }
+    if cell.italic {
+        text.push_str(r#" fo:font-style="italic""#);
+    }
+    if let Some(size) = cell.font_size {
+        text.push_str(&format!(r#" fo:font-size="{}pt""#, size));
+    }
+    if let Some(c) = &cell.font_color {
+        text.push_str(&format!(r#" fo:color="{}""#, parse_color(c)?));
+    }
+    let paragraph = if cell.center_across {
+        r#"<style:paragraph-properties fo:text-align="center"/>"#
+    } else {
+        ""
+    };
+    Ok(format!(
+        r#"<style:style style:name="{}" style:family="table-cell"><style:table-cell-properties{}/>{}<style:text-properties{}/></style:style>"#,
+        name, table, paragraph, text
+    ))
+}
+
+fn string_cell(style: &str, value: &str) -> String {
+    format!(
+        r#"<table:table-cell table:style-name="{}" office:value-type="string"><text:p>{}</text:p></table:table-cell>"#,
+        style,
+        escape_xml(value)
+    )
+}
+
+impl OutJobOds {
+    /// Writer of `<filename>.ods` with the default template.
+    pub fn new(filename: &str) -> OutJobOds {
+        Self::with_template(filename, Template::default())
+    }
+
+    /// Writer of `<filename>.ods` with the given template.
+    pub fn with_template(filename: &str, template: Template) -> OutJobOds {
+        OutJobOds {
+            filename: format!("{}.ods", filename),
+            template,
+            metadata: Metadata::default(),
+            styles: Vec::new(),
+        }
+    }
+
+    /// Project information for the cover sheet, written when enabled by the template.
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+
+    fn add_style(&mut self, cell: &CellFormat) -> Result<String, String> {
+        let name = format!("ce{}", self.styles.len());
+        self.styles.push(cell_style(&name, cell)?);
+        Ok(name)
+    }
+
+    /// The Summary table with the project information, as in the xlsx.
+    fn cover(&mut self, data: &[Item], columns_style: &mut Vec<String>) -> Result<String, String> {
+        let fmt_title = self.add_style(&self.template.cover.title.clone())?;
+        let fmt_label = self.add_style(&self.template.cover.label.clone())?;
+
+        let mut table = String::from(r#"<table:table table:name="Summary">"#);
+        for width in [20.0, 60.0].iter() {
+            let style = format!("co{}", columns_style.len());
+            columns_style.push(format!(
+                r#"<style:style style:name="{}" style:family="table-column"><style:table-column-properties style:column-width="{:.2}cm"/></style:style>"#,
+                style,
+                width * 0.2
+            ));
+            table.push_str(&format!(
+                r#"<table:table-column table:style-name="{}"/>"#,
+                style
+            ));
+        }
+        table.push_str(&format!(
+            "<table:table-row>{}</table:table-row><table:table-row><table:table-cell/></table:table-row>",
+            string_cell(&fmt_title, "Bill Of Materials")
+        ));
+        for field in cover_rows(&self.template.cover.fields, &self.metadata, data)? {
+            table.push_str("<table:table-row>");
+            match field {
+                Some((label, value)) => {
+                    table.push_str(&string_cell(&fmt_label, &label));
+                    table.push_str(&format!(
+                        r#"<table:table-cell office:value-type="string"><text:p>{}</text:p></table:table-cell>"#,
+                        escape_xml(&value)
+                    ));
+                }
+                None => table.push_str("<table:table-cell/>"),
+            }
+            table.push_str("</table:table-row>");
+        }
+        table.push_str("</table:table>");
+        Ok(table)
+    }
+
+    /// A table with the header row, category rows when enabled and the data.
+    fn table(
+        &mut self,
+        name: &str,
+        headers: &[HeaderMap],
+        data: &[Item],
+        categories: &[Category],
+        category_rows: bool,
+        columns_style: &mut Vec<String>,
+    ) -> Result<String, String> {
+        let columns = self.template.columns(headers);
+        let fmt_default = self.add_style(&self.template.data.clone())?;
+        let fmt_header = self.add_style(&self.template.header.clone())?;
+        let fmt_category = self.add_style(&self.template.category.clone())?;
+        let fmt_columns = columns
+            .iter()
+            .map(|c| c.format.as_ref().map(|f| self.add_style(f)).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut text_len: Vec<usize> = columns.iter().map(|c| c.label.chars().count()).collect();
+        let mut rows = String::new();
+        for i in categories.iter() {
+            if category_rows {
+                rows.push_str(&format!(
+                    r#"<table:table-row><table:table-cell table:style-name="{}" office:value-type="string" table:number-columns-spanned="{}"><text:p>{:?}</text:p></table:table-cell>"#,
+                    fmt_category,
+                    columns.len(),
+                    i
+                ));
+                if columns.len() > 1 {
+                    rows.push_str(&format!(
+                        r#"<table:covered-table-cell table:number-columns-repeated="{}"/>"#,
+                        columns.len() - 1
+                    ));
+                }
+                rows.push_str("</table:table-row>");
+            }
+            for item in self.template.items_of(data, i) {
+                rows.push_str("<table:table-row>");
+                for (n, col) in columns.iter().enumerate() {
+                    let value = cell_value(item, headers, &col.source);
+                    text_len[n] = text_len[n].max(value.chars().count());
+                    let style = fmt_columns[n].as_ref().unwrap_or(&fmt_default);
+                    rows.push_str(&string_cell(style, &value));
+                }
+                rows.push_str("</table:table-row>");
+            }
+        }
+
+        let mut table = format!(r#"<table:table table:name="{}">"#, escape_xml(name));
+        for (n, col) in columns.iter().enumerate() {
+            let width = col.width.unwrap_or_else(|| auto_width(text_len[n]));
+            let style = format!("co{}", columns_style.len());
+            // Width in characters, about 0.2cm each as in the xlsx
+            columns_style.push(format!(
+                r#"<style:style style:name="{}" style:family="table-column"><style:table-column-properties style:column-width="{:.2}cm"/></style:style>"#,
+                style,
+                width * 0.2
+            ));
+            table.push_str(&format!(
+                r#"<table:table-column table:style-name="{}"/>"#,
+                style
+            ));
+        }
+        // Header row repeated on each printed page
+        table.push_str("<table:table-header-rows><table:table-row>");
+        for (n, col) in columns.iter().enumerate() {
+            table.push_str(&string_cell(
+                fmt_columns[n].as_ref().unwrap_or(&fmt_header),
+                &col.label,
+            ));
+        }
+        table.push_str("</table:table-row></table:table-header-rows>");
+        table.push_str(&rows);
+        table.push_str("</table:table>");
+        Ok(table)
+    }
+
+    /// Write the same sheets of `OutJobXlsx::write`: the cover, the BOM and
+    /// the category and side sheets the template enables.
+    ///
+    /// Errors when the file can not be written, or on an invalid color or
+    /// cover field in the template.
+    pub fn write(
+        mut self,
+        headers: &[HeaderMap],
+        data: &[Item],
+        categories: Vec<Category>,
+    ) -> Result<(), String> {
+        let mut columns_style = Vec::new();
+        let mut tables = String::new();
+        if self.template.cover.enabled {
+            tables.push_str(&self.cover(data, &mut columns_style)?);
+        }
+        tables.push_str(&self.table(
+            "BOM",
+            headers,
+            data,
+            &categories,
+            self.template.category_rows,
+            &mut columns_style,
+        )?);
+        if self.template.category_sheets {
+            for c in categories.iter() {
+                tables.push_str(&self.table(
+                    format!("{:?}", c).as_str(),
+                    headers,
+                    data,
+                    std::slice::from_ref(c),
+                    false,
+                    &mut columns_style,
+                )?);
+            }
+        }
+        if self.template.side_sheets {
+            for (side, items, side_categories) in side_items(data, &categories) {
+                tables.push_str(&self.table(
+                    format!("BOM {}", side).as_str(),
+                    headers,
+                    &items,
+                    &side_categories,
+                    self.template.category_rows,
+                    &mut columns_style,
+                )?);
+            }
+        }
+
+        let content = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" office:version="1.2"><office:automatic-styles>{}{}</office:automatic-styles><office:body><office:spreadsheet>{}</office:spreadsheet></office:body></office:document-content>
+"#,
+            columns_style.join(""),
+            self.styles.join(""),
+            tables
+        );
+
+        let file = match File::create(&self.filename) {
+            Ok(f) => f,
+            Err(error) => return Err(format!("Unable to create {}: {}", self.filename, error)),
+        };
+        let mut zip = ZipWriter::new(file);
+        // The mimetype goes first and uncompressed, to be recognized as ods
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let result = zip
+            .start_file("mimetype", stored)
+            .and_then(|_| Ok(zip.write_all(b"application/vnd.oasis.opendocument.spreadsheet")?))
+            .and_then(|_| zip.start_file("META-INF/manifest.xml", FileOptions::default()))
+            .and_then(|_| Ok(zip.write_all(MANIFEST.as_bytes())?))
+            .and_then(|_| zip.start_file("content.xml", FileOptions::default()))
+            .and_then(|_| Ok(zip.write_all(content.as_bytes())?))
+            .and_then(|_| zip.finish().map(|_| ()));
+        result.map_err(|error| format!("Unable to write {}: {}", self.filename, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::DataParser;
+    use crate::load::{temp_path, InputFormat, Load};
+    use calamine::{open_workbook_auto, Reader};
+
+    #[test]
+    fn test_ods_layout() {
+        let data = DataParser::new(Load::new("test_data/bom0.xlsx").unwrap()).unwrap();
+        let path = temp_path("ods_layout");
+        let path = path.as_str();
+        OutJobOds::new(path)
+            .write(data.headers(), data.items(), data.categories())
+            .unwrap();
+
+        let content = std::fs::read(format!("{}.ods", path)).unwrap();
+        assert_eq!(InputFormat::detect(&content), InputFormat::Ods);
+
+        let mut wk = open_workbook_auto(format!("{}.ods", path)).unwrap();
+        let range = wk.worksheet_range("BOM").unwrap().unwrap();
+        let cell = |r: usize, c: usize| match range.get((r, c)) {
+            Some(v) => v.to_string(),
+            None => String::new(),
+        };
+        assert_eq!(cell(0, 0), "Qty");
+        assert_eq!(cell(0, 1), "Designator");
+        assert_eq!(cell(1, 0), "Connectors");
+        assert_eq!(cell(2, 1), "J1");
+        assert_eq!(cell(4, 0), "Resistors");
+        assert_eq!(cell(5, 2), "10k");
+
+        let mut template = Template::default();
+        template.cover.enabled = true;
+        template.side_sheets = true;
+        let mut out = OutJobOds::with_template(path, template);
+        out.set_metadata(Metadata {
+            project: String::from("Motor driver"),
+            ..Default::default()
+        });
+        out.write(data.headers(), data.items(), data.categories())
+            .unwrap();
+
+        let mut wk = open_workbook_auto(format!("{}.ods", path)).unwrap();
+        assert_eq!(
+            wk.sheet_names().to_vec(),
+            vec!["Summary", "BOM", "BOM Top", "BOM Bottom"]
+        );
+        let range = wk.worksheet_range("Summary").unwrap().unwrap();
+        assert_eq!(range.get((0, 0)).unwrap().to_string(), "Bill Of Materials");
+        assert_eq!(range.get((2, 0)).unwrap().to_string(), "Project");
+        assert_eq!(range.get((2, 1)).unwrap().to_string(), "Motor driver");
+
+        let mut template = Template::default();
+        template.header.bg_color = Some(String::from("cyann"));
+        assert_eq!(
+            OutJobOds::with_template(path, template)
+                .write(data.headers(), data.items(), data.categories())
+                .err(),
+            Some(String::from("Invalid color [cyann]"))
+        );
+        std::fs::remove_file(format!("{}.ods", path)).unwrap();
+    }
+}
@@ -0,0 +1,472 @@
+use super::items::{Category, HeaderMap, Item, Side};
+use super::metadata::Metadata;
+use super::stats::StatsReport;
+use super::template::{auto_width, cell_value, CellFormat, Template};
+use xlsxwriter::*;
+
+/// xlsx writer of a merged BOM, laid out by a `Template`.
+pub struct OutJobXlsx {
+    filename: String,
+    wk: Workbook,
+    template: Template,
+    metadata: Metadata,
+}
+
+fn color(name: &str) -> Result<FormatColor, String> {
+    Ok(match name.to_lowercase().as_str() {
+        "black" => FormatColor::Black,
+        "blue" => FormatColor::Blue,
+        "brown" => FormatColor::Brown,
+        "cyan" => FormatColor::Cyan,
+        "gray" | "grey" => FormatColor::Gray,
+        "green" => FormatColor::Green,
+        "lime" => FormatColor::Lime,
+        "magenta" => FormatColor::Magenta,
+        "navy" => FormatColor::Navy,
+        "orange" => FormatColor::Orange,
+        "purple" => FormatColor::Purple,
+        "red" => FormatColor::Red,
+        "pink" => FormatColor::Pink,
+        "silver" => FormatColor::Silver,
+        "white" => FormatColor::White,
+        "yellow" => FormatColor::Yellow,
+        hex => match u32::from_str_radix(hex.trim_start_matches('#'), 16) {
+            Ok(rgb) => FormatColor::Custom(rgb),
+            Err(_) => return Err(format!("Invalid color [{}]", name)),
+        },
+    })
+}
+
+fn add_format<'a>(wk: &'a Workbook, cell: &CellFormat) -> Result<Format<'a>, String> {
+    let mut fmt = wk.add_format();
+    if cell.bold {
+        fmt = fmt.set_bold();
+    }
+    if cell.italic {
+        fmt = fmt.set_italic();
+    }
+    if cell.text_wrap {
+        fmt = fmt.set_text_wrap();
+    }
+    if cell.border {
+        fmt = fmt.set_border(FormatBorder::Thin);
+    }
+    if cell.center_across {
+        fmt = fmt.set_align(FormatAlignment::CenterAcross);
+    }
+    if let Some(size) = cell.font_size {
+        fmt = fmt.set_font_size(size);
+    }
+    if let Some(c) = &cell.font_color {
+        fmt = fmt.set_font_color(color(c)?);
+    }
+    if let Some(c) = &cell.bg_color {
+        fmt = fmt.set_bg_color(color(c)?);
+    }
+    Ok(fmt)
+}
+
+/// Label and value of each cover row, None for the blank rows between groups.
+pub(crate) fn cover_rows(
+    fields: &[String],
+    metadata: &Metadata,
+    data: &[Item],
+) -> Result<Vec<Option<(String, String)>>, String> {
+    let field = |label: &str, value: &str| Some((label.to_string(), value.to_string()));
+    let mut rows = Vec::new();
+    for f in fields.iter() {
+        match f.to_lowercase().as_str() {
+            "project" => rows.push(field("Project", &metadata.project)),
+            "revision" => rows.push(field("Revision", &metadata.revision)),
+            "variant" if !metadata.variant.is_empty() => {
+                rows.push(field("Variant", &metadata.variant))
+            }
+            "variant" => (),
+            "date" => rows.push(field("Date", &metadata.date())),
+            "author" => rows.push(field("Author", &metadata.author)),
+            "inputs" => {
+                rows.push(None);
+                rows.push(field("Input files", "sha256"));
+                for i in metadata.inputs.iter() {
+                    rows.push(field(&i.path, &i.sha256));
+                }
+            }
+            "stats" => {
+                rows.push(None);
+                rows.push(field("Category", "Lines / Placements"));
+                let report = StatsReport::new(data);
+                for s in report.categories.iter() {
+                    let value = format!("{} / {}", s.counts.lines, s.counts.placements);
+                    rows.push(field(&format!("{:?}", s.category), &value));
+                }
+            }
+            _ => return Err(format!("Invalid cover field [{}]", f)),
+        }
+    }
+    Ok(rows)
+}
+
+/// Items and categories placed on each board side, for the side sheets.
+pub(crate) fn side_items(
+    data: &[Item],
+    categories: &[Category],
+) -> Vec<(Side, Vec<Item>, Vec<Category>)> {
+    let mut sides = Vec::new();
+    for side in [Side::Top, Side::Bottom].iter() {
+        let items = data
+            .iter()
+            .filter_map(|m| m.on_side(*side))
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            continue;
+        }
+        let side_categories = categories
+            .iter()
+            .filter(|c| items.iter().any(|m| m.category == **c))
+            .cloned()
+            .collect::<Vec<_>>();
+        sides.push((*side, items, side_categories));
+    }
+    sides
+}
+
+impl OutJobXlsx {
+    /// Writer of `<filename>.xlsx` with the default template.
+    pub fn new(filename: &str) -> OutJobXlsx {
+        Self::with_template(filename, Template::default())
+    }
+
+    /// Writer of `<filename>.xlsx` with the given template.
+    pub fn with_template(filename: &str, template: Template) -> OutJobXlsx {
+        let filename = format!("{}.xlsx", filename);
+        OutJobXlsx {
+            wk: Workbook::new(filename.as_str()),
+            filename,
+            template,
+            metadata: Metadata::default(),
+        }
+    }
+
+    /// Project information for the cover sheet, written when enabled by the template.
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+
+    /// Write a BOM worksheet with the given categories, with a frozen header
+    /// and an autofilter over the data.
+    fn write_sheet(
+        &self,
+        name: &str,
+        headers: &[HeaderMap],
+        data: &[Item],
+        categories: &[Category],
+        category_rows: bool,
+    ) -> Result<(), String> {
+        let wk = &self.wk;
+        let columns = self.template.columns(headers);
+
+        let fmt_defalt = add_format(wk, &self.template.data)?;
+        let fmt_header = add_format(wk, &self.template.header)?;
+        let fmt_category = add_format(wk, &self.template.category)?;
+        let fmt_columns: Vec<Option<Format>> = columns
+            .iter()
+            .map(|c| c.format.as_ref().map(|f| add_format(wk, f)).transpose())
+            .collect::<Result<_, _>>()?;
+
+        let mut sheet = wk.add_worksheet(Some(name)).map_err(|e| self.error(e))?;
+
+        // Longest text in each column, to size it when the template does not
+        let mut text_len: Vec<usize> = columns.iter().map(|c| c.label.chars().count()).collect();
+
+        let mut row: u32 = 0;
+        for (n, col) in columns.iter().enumerate() {
+            sheet
+                .write_string(
+                    row,
+                    n as u16,
+                    col.label.as_str(),
+                    Some(fmt_columns[n].as_ref().unwrap_or(&fmt_header)),
+                )
+                .map_err(|e| self.error(e))?;
+        }
+        row += 1;
+        for i in categories.iter() {
+            // Write Category Header
+            if category_rows {
+                sheet
+                    .merge_range(
+                        row,
+                        0,
+                        row,
+                        columns.len().saturating_sub(1) as u16,
+                        format!("{:?}", i).as_str(),
+                        Some(&fmt_category),
+                    )
+                    .map_err(|e| self.error(e))?;
+                row += 1;
+            }
+            for item in self.template.items_of(data, i) {
+                for (n, col) in columns.iter().enumerate() {
+                    let value = cell_value(item, headers, &col.source);
+                    text_len[n] = text_len[n].max(value.chars().count());
+                    sheet
+                        .write_string(
+                            row,
+                            n as u16,
+                            value.as_str(),
+                            Some(fmt_columns[n].as_ref().unwrap_or(&fmt_defalt)),
+                        )
+                        .map_err(|e| self.error(e))?;
+                }
+                row += 1;
+            }
+        }
+
+        for (n, col) in columns.iter().enumerate() {
+            let width = match col.width {
+                Some(w) => w,
+                None => auto_width(text_len[n]),
+            };
+            sheet
+                .set_column(n as u16, n as u16, width, None)
+                .map_err(|e| self.error(e))?;
+        }
+
+        if !columns.is_empty() {
+            sheet.freeze_panes(1, 0);
+            sheet
+                .autofilter(0, 0, row - 1, columns.len() as u16 - 1)
+                .map_err(|e| self.error(e))?;
+        }
+
+        // Print setup: landscape A4 fit to page width, header row on every page
+        sheet.set_landscape();
+        sheet.set_paper(PaperType::A4);
+        sheet.fit_to_pages(1, 0);
+        sheet.repeat_rows(0, 0).map_err(|e| self.error(e))?;
+        sheet
+            .set_footer("&L&F&RPage &P of &N")
+            .map_err(|e| self.error(e))?;
+        Ok(())
+    }
+
+    fn write_cover(&self, data: &[Item]) -> Result<(), String> {
+        let cover = &self.template.cover;
+        let fmt_title = add_format(&self.wk, &cover.title)?;
+        let fmt_label = add_format(&self.wk, &cover.label)?;
+
+        let mut sheet = self
+            .wk
+            .add_worksheet(Some("Summary"))
+            .map_err(|e| self.error(e))?;
+        sheet
+            .set_column(0, 0, 20.0, None)
+            .map_err(|e| self.error(e))?;
+        sheet
+            .set_column(1, 1, 60.0, None)
+            .map_err(|e| self.error(e))?;
+
+        sheet
+            .write_string(0, 0, "Bill Of Materials", Some(&fmt_title))
+            .map_err(|e| self.error(e))?;
+        let rows = cover_rows(&cover.fields, &self.metadata, data)?;
+        for (row, field) in (2u32..).zip(rows) {
+            if let Some((label, value)) = field {
+                sheet
+                    .write_string(row, 0, &label, Some(&fmt_label))
+                    .map_err(|e| self.error(e))?;
+                sheet
+                    .write_string(row, 1, &value, None)
+                    .map_err(|e| self.error(e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the cover, the BOM sheet and the category and side sheets the
+    /// template enables, then close the workbook.
+    ///
+    /// Errors when the file can not be written, or on an invalid color or
+    /// cover field in the template.
+    pub fn write(
+        self,
+        headers: &[HeaderMap],
+        data: &[Item],
+        categories: Vec<Category>,
+    ) -> Result<(), String> {
+        if self.template.cover.enabled {
+            self.write_cover(data)?;
+        }
+
+        self.write_sheet(
+            "BOM",
+            headers,
+            data,
+            &categories,
+            self.template.category_rows,
+        )?;
+        if self.template.category_sheets {
+            for c in categories.iter() {
+                self.write_sheet(
+                    format!("{:?}", c).as_str(),
+                    headers,
+                    data,
+                    std::slice::from_ref(c),
+                    false,
+                )?;
+            }
+        }
+        if self.template.side_sheets {
+            for (side, items, side_categories) in side_items(data, &categories) {
+                self.write_sheet(
+                    format!("BOM {}", side).as_str(),
+                    headers,
+                    &items,
+                    &side_categories,
+                    self.template.category_rows,
+                )?;
+            }
+        }
+
+        let filename = self.filename;
+        self.wk
+            .close()
+            .map_err(|error| format!("Unable to write {}: {}", filename, error))
+    }
+
+    fn error(&self, error: XlsxError) -> String {
+        format!("Unable to write {}: {}", self.filename, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::DataParser;
+    use crate::load::{temp_path, Load};
+    use calamine::{open_workbook_auto, Reader};
+
+    #[test]
+    fn test_extra_columns_placement() {
+        let data = DataParser::new(Load::new("test_data/bom0.xlsx").unwrap()).unwrap();
+        let path = temp_path("columns");
+        let path = path.as_str();
+        OutJobXlsx::new(path)
+            .write(data.headers(), data.items(), data.categories())
+            .unwrap();
+
+        let mut wk = open_workbook_auto(format!("{}.xlsx", path)).unwrap();
+        let range = wk.worksheet_range("BOM").unwrap().unwrap();
+        let cell = |r: usize, c: usize| match range.get((r, c)) {
+            Some(v) => v.to_string(),
+            None => String::new(),
+        };
+        let width = range.get_size().1;
+        let hdr = (0..width).map(|c| cell(0, c)).collect::<Vec<_>>();
+        let column = |label: &str| hdr.iter().position(|m| m == label).unwrap();
+        let row = |des: &str| {
+            (1..range.get_size().0)
+                .find(|r| cell(*r, column("Designator")) == des)
+                .unwrap()
+        };
+
+        assert_eq!(hdr[0], "Qty");
+        let c2 = row("C2, C3, C4");
+        assert_eq!(cell(c2, 0), "3");
+        assert_eq!(cell(c2, column("Code farnell")), "53634");
+        assert_eq!(cell(c2, column("Code mouser")), "fgjldkfj");
+        assert_eq!(cell(c2, column("Note description")), "45dg345");
+        assert_eq!(cell(c2, column("Code digikey")), "6ubfgb");
+        assert_eq!(cell(c2, column("Layer")), "Bottom");
+        assert_eq!(cell(c2, column("Mount Technology")), "THT");
+        assert_eq!(cell(c2, column("Footprint")), "1206_[3216]");
+
+        let j1 = row("J1");
+        assert_eq!(cell(j1, column("Code farnell")), "");
+        assert_eq!(cell(j1, column("Note description")), "980809");
+        assert_eq!(cell(j1, column("Layer")), "Top");
+        std::fs::remove_file(format!("{}.xlsx", path)).unwrap();
+    }
+
+    #[test]
+    fn test_category_sheets() {
+        let data = DataParser::new(Load::new("test_data/bom0.xlsx").unwrap()).unwrap();
+        let path = temp_path("category_sheets");
+        let path = path.as_str();
+        let template = Template {
+            category_sheets: true,
+            ..Default::default()
+        };
+        OutJobXlsx::with_template(path, template)
+            .write(data.headers(), data.items(), data.categories())
+            .unwrap();
+
+        let mut wk = open_workbook_auto(format!("{}.xlsx", path)).unwrap();
+        assert_eq!(
+            wk.sheet_names().to_owned(),
+            vec![
+                "BOM",
+                "Connectors",
+                "Resistors",
+                "Capacitors",
+                "Diode",
+                "Inductors"
+            ]
+        );
+
+        let range = wk.worksheet_range("Resistors").unwrap().unwrap();
+        assert_eq!(range.get_size().0, 5);
+        assert_eq!(range.get((0, 1)).unwrap().to_string(), "Designator");
+        // No category separator rows, data right below the header, sorted by value
+        assert_eq!(
+            range.get((1, 1)).unwrap().to_string(),
+            "R1, R2, R3, R4, R5, R6, R7, R8, R9, R10"
+        );
+        assert_eq!(range.get((3, 2)).unwrap().to_string(), "15k");
+
+        let mut template = Template::default();
+        template.cover.enabled = true;
+        template.cover.fields = vec![String::from("project"), String::from("budget")];
+        assert_eq!(
+            OutJobXlsx::with_template(path, template)
+                .write(data.headers(), data.items(), data.categories())
+                .err(),
+            Some(String::from("Invalid cover field [budget]"))
+        );
+        std::fs::remove_file(format!("{}.xlsx", path)).unwrap();
+    }
+
+    #[test]
+    fn test_side_sheets() {
+        let data = DataParser::new(Load::new("test_data/bom0.xlsx").unwrap()).unwrap();
+        let path = temp_path("side_sheets");
+        let path = path.as_str();
+        let template = Template {
+            side_sheets: true,
+            category_rows: false,
+            ..Default::default()
+        };
+        OutJobXlsx::with_template(path, template)
+            .write(data.headers(), data.items(), data.categories())
+            .unwrap();
+
+        let mut wk = open_workbook_auto(format!("{}.xlsx", path)).unwrap();
+        assert_eq!(
+            wk.sheet_names().to_owned(),
+            vec!["BOM", "BOM Top", "BOM Bottom"]
+        );
+
+        let column = |range: &calamine::Range<calamine::DataType>, c: usize| {
+            (1..range.get_size().0)
+                .map(|r| range.get((r, c)).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let top = wk.worksheet_range("BOM Top").unwrap().unwrap();
+        assert!(column(&top, 1).contains(&String::from("C0, C1")));
+        assert!(column(&top, 6).iter().all(|m| m == "Top"));
+        let bottom = wk.worksheet_range("BOM Bottom").unwrap().unwrap();
+        assert!(column(&bottom, 1).contains(&String::from("R12, R13")));
+        assert!(column(&bottom, 6).iter().all(|m| m == "Bottom"));
+        std::fs::remove_file(format!("{}.xlsx", path)).unwrap();
+    }
+}
@@ -0,0 +1,413 @@
+use log::info;
+use std::fs;
+
+use super::items::{Item, Side};
+
+/// A part in the pick-and-place file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub designator: String,
+    pub x: f64,
+    pub y: f64,
+    pub rotation: f64,
+    pub side: Option<Side>,
+    /// Line in the source file, to write it back unchanged.
+    line: usize,
+}
+
+/// Difference between the BOM and the pick-and-place file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PnpIssue {
+    /// Designator in the BOM without a placement.
+    MissingInPnp(String),
+    /// Placement without a designator in the BOM.
+    MissingInBom(String),
+    LayerMismatch {
+        designator: String,
+        bom: Side,
+        pnp: Side,
+    },
+    /// Not populated part which has a placement.
+    NotFitted(String),
+}
+
+/// Pick-and-place (centroid) file, Altium CSV or text, KiCad .pos / .csv.
+///
+/// The header is the first line with a designator and the X/Y columns, lines
+/// before it and comments are kept as they are in the filtered output.
+pub struct PickPlace {
+    lines: Vec<String>,
+    placements: Vec<Placement>,
+}
+
+#[derive(Default)]
+struct PnpColumns {
+    designator: usize,
+    x: usize,
+    y: usize,
+    rotation: Option<usize>,
+    side: Option<usize>,
+    /// Comma separated file, else text aligned in columns.
+    csv: bool,
+    /// Character where each column of a text header starts.
+    starts: Vec<usize>,
+}
+
+impl PickPlace {
+    /// Read a pick-and-place file. Errors when it can not be read, has no
+    /// header or a row with invalid coordinates.
+    pub fn new(filename: &str) -> Result<PickPlace, String> {
+        info!("Parse pick and place: {}", filename);
+        match fs::read_to_string(filename) {
+            Ok(text) => Self::parse(&text).map_err(|e| format!("{}: {}", filename, e)),
+            Err(error) => Err(format!(
+                "Unable to read pick and place {}: {}",
+                filename, error
+            )),
+        }
+    }
+
+    fn parse(text: &str) -> Result<PickPlace, String> {
+        let lines = text.lines().map(String::from).collect::<Vec<_>>();
+        let mut columns: Option<PnpColumns> = None;
+        let mut placements = Vec::new();
+
+        for (n, line) in lines.iter().enumerate() {
+            let cols = match &columns {
+                Some(c) => c,
+                None => {
+                    columns = Self::match_header(line);
+                    continue;
+                }
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty()
+                || trimmed.starts_with('#')
+                || trimmed.chars().all(|c| c == '-' || c == '=' || c == ' ')
+            {
+                continue;
+            }
+
+            let fields = Self::fields(line, cols);
+            let designator = match fields.get(cols.designator) {
+                Some(d) if !d.is_empty() => d.clone(),
+                _ => continue,
+            };
+            let field = |index: usize, name: &str| {
+                fields
+                    .get(index)
+                    .map(|v| v.as_str())
+                    .ok_or_else(|| format!("Missing {} of {} at line {}", name, designator, n + 1))
+            };
+            let number = |index: usize, name: &str| {
+                let value = field(index, name)?;
+                value
+                    .trim_end_matches(|c: char| c.is_alphabetic())
+                    .parse::<f64>()
+                    .map_err(|_| format!("Invalid {} [{}] at line {}", name, value, n + 1))
+            };
+            placements.push(Placement {
+                x: number(cols.x, "X")?,
+                y: number(cols.y, "Y")?,
+                rotation: match cols.rotation {
+                    Some(i) => number(i, "rotation")?,
+                    None => 0.0,
+                },
+                side: match cols.side {
+                    Some(i) => Some(
+                        field(i, "layer")?
+                            .parse()
+                            .map_err(|e| format!("{} at line {}", e, n + 1))?,
+                    ),
+                    None => None,
+                },
+                designator,
+                line: n,
+            });
+        }
+
+        if columns.is_none() {
+            return Err(String::from("Unable to find pick and place header"));
+        }
+        Ok(PickPlace { lines, placements })
+    }
+
+    /// Columns of the line, if it is the header. Text headers are split on
+    /// two or more spaces first, so that names like `Mid X` stay whole, then
+    /// on any space.
+    fn match_header(line: &str) -> Option<PnpColumns> {
+        if line.contains(',') {
+            let names = Self::split_csv(line);
+            return Self::match_columns(&names).map(|cols| PnpColumns { csv: true, ..cols });
+        }
+        let candidates = vec![
+            Self::split_text(line, 2),
+            Self::join_axes(Self::split_text(line, 1)),
+            Self::split_text(line, 1),
+        ];
+        candidates.into_iter().find_map(|fields| {
+            let (starts, names): (Vec<usize>, Vec<String>) = fields.into_iter().unzip();
+            Self::match_columns(&names).map(|cols| PnpColumns { starts, ..cols })
+        })
+    }
+
+    /// Join the `X` and `Y` to the `Mid`, `Ref` and `Pad` before them, for the
+    /// Altium text headers which use a single space between columns too.
+    fn join_axes(fields: Vec<(usize, String)>) -> Vec<(usize, String)> {
+        let mut joined: Vec<(usize, String)> = Vec::new();
+        for (n, f) in fields {
+            let axis = f.eq_ignore_ascii_case("x") || f.eq_ignore_ascii_case("y");
+            match joined.last_mut() {
+                Some((_, prev))
+                    if axis && ["mid", "ref", "pad"].contains(&prev.to_lowercase().as_str()) =>
+                {
+                    prev.push(' ');
+                    prev.push_str(&f);
+                }
+                _ => joined.push((n, f)),
+            }
+        }
+        joined
+    }
+
+    /// Fields of a data row. Text rows are split on spaces when they give a
+    /// field for each column, otherwise cut at the columns of the header, as
+    /// in Altium text files with empty fields or spaces in the values.
+    fn fields(line: &str, cols: &PnpColumns) -> Vec<String> {
+        if cols.csv {
+            return Self::split_csv(line);
+        }
+        let fields = line
+            .split_whitespace()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        if fields.len() == cols.starts.len() {
+            return fields;
+        }
+        let chars = line.chars().collect::<Vec<_>>();
+        let cut = |n: usize| {
+            cols.starts
+                .get(n)
+                .map_or(chars.len(), |&m| m.min(chars.len()))
+        };
+        (0..cols.starts.len())
+            .map(|n| {
+                chars[cut(n)..cut(n + 1)]
+                    .iter()
+                    .collect::<String>()
+                    .trim()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    /// Fields of a comma separated line, with quotes.
+    fn split_csv(line: &str) -> Vec<String> {
+        let line = line.trim().trim_start_matches('#').trim();
+        let mut rd = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(line.as_bytes());
+        match rd.records().next() {
+            Some(Ok(record)) => record.iter().map(|m| m.trim().to_string()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Fields of a text line with the character they start at, separated by
+    /// at least `gap` spaces or a tab. Leading `#` of comments are skipped.
+    fn split_text(line: &str, gap: usize) -> Vec<(usize, String)> {
+        let mut fields: Vec<(usize, String)> = Vec::new();
+        let mut spaces = gap;
+        for (n, c) in line.chars().enumerate() {
+            if c == '\t' {
+                spaces += gap;
+            } else if c.is_whitespace() || (c == '#' && fields.is_empty()) {
+                spaces += 1;
+            } else {
+                match fields.last_mut() {
+                    Some((_, f)) if spaces < gap => {
+                        f.push_str(&" ".repeat(spaces));
+                        f.push(c);
+                    }
+                    _ => fields.push((n, c.to_string())),
+                }
+                spaces = 0;
+            }
+        }
+        fields
+    }
+
+    fn match_columns(fields: &[String]) -> Option<PnpColumns> {
+        let mut cols = PnpColumns::default();
+        let (mut designator, mut x, mut y) = (None, None, None);
+        for (n, f) in fields.iter().enumerate() {
+            match f.to_lowercase().as_str() {
+                "designator" | "ref" | "refdes" | "reference" => designator = Some(n),
+                "posx" | "x" | "mid x" => x = Some(n),
+                "posy" | "y" | "mid y" => y = Some(n),
+                "rotation" | "rot" => cols.rotation = Some(n),
+                "layer" | "side" | "tb" => cols.side = Some(n),
+                lower if lower.starts_with("center-x") => x = Some(n),
+                lower if lower.starts_with("center-y") => y = Some(n),
+                _ => (),
+            }
+        }
+        cols.designator = designator?;
+        cols.x = x?;
+        cols.y = y?;
+        Some(cols)
+    }
+
+    /// Parts of the file, in file order.
+    pub fn placements(&self) -> &[Placement] {
+        &self.placements
+    }
+
+    /// Compare the placements with the BOM items.
+    pub fn check(&self, items: &[Item]) -> Vec<PnpIssue> {
+        let mut issues = Vec::new();
+        for item in items {
+            for (n, des) in item.designator.iter().enumerate() {
+                let placement = self.placements.iter().find(|m| m.designator == *des);
+                match placement {
+                    None if item.is_fitted() => issues.push(PnpIssue::MissingInPnp(des.clone())),
+                    None => (),
+                    Some(_) if !item.is_fitted() => issues.push(PnpIssue::NotFitted(des.clone())),
+                    Some(p) => {
+                        if let (Some(Some(bom)), Some(pnp)) = (item.side.get(n), p.side) {
+                            if *bom != pnp {
+                                issues.push(PnpIssue::LayerMismatch {
+                                    designator: des.clone(),
+                                    bom: *bom,
+                                    pnp,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for p in self.placements.iter() {
+            if !items.iter().any(|m| m.designator.contains(&p.designator)) {
+                issues.push(PnpIssue::MissingInBom(p.designator.clone()));
+            }
+        }
+        issues
+    }
+
+    /// Same file content with only the placements of fitted BOM parts.
+    pub fn fitted(&self, items: &[Item]) -> String {
+        let fitted = |des: &str| {
+            items
+                .iter()
+                .any(|m| m.is_fitted() && m.designator.iter().any(|d| d == des))
+        };
+        let mut out = String::new();
+        for (n, line) in self.lines.iter().enumerate() {
+            let keep = match self.placements.iter().find(|m| m.line == n) {
+                Some(p) => fitted(&p.designator),
+                None => true,
+            };
+            if keep {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// Write the file of `fitted` to `filename`.
+    pub fn write_fitted(&self, filename: &str, items: &[Item]) -> Result<(), String> {
+        fs::write(filename, self.fitted(items))
+            .map_err(|error| format!("Unable to write {}: {}", filename, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::DataParser;
+    use crate::load::Load;
+
+    #[test]
+    fn test_pnp_check() {
+        let data = DataParser::new(Load::new("test_data/bom0.xlsx").unwrap()).unwrap();
+        let pnp = PickPlace::new("test_data/pnp0.csv").unwrap();
+        assert_eq!(pnp.placements().len(), 26);
+        assert_eq!(pnp.placements()[0].x, 5.08);
+        assert_eq!(pnp.placements()[0].side, Some(Side::Top));
+
+        // Make C5, C6 not populated
+        let mut items = data.items().to_vec();
+        let c5 = items
+            .iter_mut()
+            .find(|m| m.designator.contains(&String::from("C5")))
+            .unwrap();
+        c5.comment = String::from("NP");
+        c5.base_exp = (-1.0, 0);
+
+        assert_eq!(
+            pnp.check(&items),
+            vec![
+                PnpIssue::NotFitted(String::from("C5")),
+                PnpIssue::LayerMismatch {
+                    designator: String::from("C2"),
+                    bom: Side::Bottom,
+                    pnp: Side::Top
+                },
+                PnpIssue::MissingInBom(String::from("TP1")),
+            ]
+        );
+
+        let fitted = pnp.fitted(&items);
+        assert!(fitted.contains("Units used: mm"));
+        assert!(fitted.contains("\"C4\""));
+        assert!(!fitted.contains("\"C5\""));
+        assert!(!fitted.contains("\"TP1\""));
+    }
+
+    #[test]
+    fn test_pnp_kicad() {
+        let pnp = PickPlace::new("test_data/pnp0.pos").unwrap();
+        assert_eq!(
+            pnp.placements()
+                .iter()
+                .map(|m| (m.designator.as_str(), m.y, m.rotation, m.side))
+                .collect::<Vec<_>>(),
+            vec![
+                ("C0", -3.0, 270.0, Some(Side::Top)),
+                ("R12", -8.0, 180.0, Some(Side::Bottom))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pnp_altium_text() {
+        let pnp = PickPlace::new("test_data/pnp0.txt").unwrap();
+        assert_eq!(
+            pnp.placements()
+                .iter()
+                .map(|m| (m.designator.as_str(), m.x, m.y, m.rotation, m.side))
+                .collect::<Vec<_>>(),
+            vec![
+                ("J1", 200.0, 400.0, 90.0, Some(Side::Top)),
+                ("R1", 472.44, 118.11, 0.0, Some(Side::Top)),
+                ("C2", 590.55, 236.22, 180.0, Some(Side::Bottom))
+            ]
+        );
+
+        let text = "Ref  PosX  PosY  Rot  Side\nR1  1.0  2.0  90  top\nR2  abc  2.0  0  top\n";
+        assert_eq!(
+            PickPlace::parse(text).err(),
+            Some(String::from("Invalid X [abc] at line 3"))
+        );
+        let text = "Ref  PosX  PosY  Rot  Side\nR1  1.0  2.0  90  middle\n";
+        assert_eq!(
+            PickPlace::parse(text).err(),
+            Some(String::from("Invalid layer [middle] at line 2"))
+        );
+        assert!(PickPlace::parse("no header\n").is_err());
+        assert!(PickPlace::new("test_data/missing.pos").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use super::metadata::Metadata;
+
+/// An input BOM of the project.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectInput {
+    pub path: String,
+    /// Input format, guessed from extension or content when not given.
+    pub format: Option<String>,
+    /// Worksheet to read, the first one when not given.
+    pub sheet: Option<String>,
+    /// Boards built from this BOM, the quantities are multiplied by it.
+    #[serde(default = "one")]
+    pub multiplier: usize,
+    /// Assembly variant: the inputs of a variant are merged apart from the
+    /// others, in outputs with the variant added to their name.
+    pub variant: Option<String>,
+}
+
+/// An output of the merged BOM, the format from the extension when not given.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectOutput {
+    pub path: String,
+    pub format: Option<String>,
+}
+
+/// Inputs, outputs and options of a merge, loaded from a `bomerge.toml` like:
+///
+/// ```toml
+/// project = "Motor driver"
+/// revision = "B"
+/// template = "template.toml"
+/// merge_key = "mpn"
+/// category_sheets = true
+///
+/// [[input]]
+/// path = "power.xlsx"
+/// sheet = "BOM"
+/// multiplier = 2
+/// variant = "24V"
+///
+/// [categories]
+/// TP = "Mechanicals"
+/// RV = "Resistors"
+///
+/// [[output]]
+/// path = "merged_bom.xlsx"
+/// [[output]]
+/// path = "merged_bom.html"
+/// ```
+///
+/// Relative paths are from the directory of the project file. The command
+/// line flags, when given, take the place of these values. `bomerge --project
+/// bomerge.toml` merges it, `check` and `stats` only read its inputs.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Project {
+    /// Project, revision, author and date, as in the project metadata file.
+    #[serde(flatten)]
+    pub metadata: Metadata,
+    pub input: Vec<ProjectInput>,
+    pub output: Vec<ProjectOutput>,
+    pub template: Option<String>,
+    pub merge_key: Option<String>,
+    pub undesignated_category: Option<String>,
+    /// Category of designator prefixes, in place of the built in ones.
+    pub categories: BTreeMap<String, String>,
+    pub category_sheets: bool,
+    pub side_sheets: bool,
+    pub cover: bool,
+    pub pnp: Option<String>,
+    pub pnp_out: Option<String>,
+    pub stats_format: Option<String>,
+    /// Rules file for the check command.
+    pub rules: Option<String>,
+}
+
+fn one() -> usize {
+    1
+}
+
+impl ProjectInput {
+    /// Input BOM with the default options, as given on the command line.
+    pub fn new(path: &str) -> ProjectInput {
+        ProjectInput {
+            path: String::from(path),
+            format: None,
+            sheet: None,
+            multiplier: 1,
+            variant: None,
+        }
+    }
+}
+
+impl Project {
+    /// Load a project file, its paths resolved from the file directory.
+    pub fn new(filename: &str) -> Result<Project, String> {
+        let text = match fs::read_to_string(filename) {
+            Ok(t) => t,
+            Err(error) => return Err(format!("Unable to read project {}: {}", filename, error)),
+        };
+        let mut project: Project = match toml::from_str(&text) {
+            Ok(p) => p,
+            Err(error) => return Err(format!("Invalid project {}: {}", filename, error)),
+        };
+
+        let dir = Path::new(filename)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let resolve = |path: &mut String| {
+            if path != "-" && Path::new(path.as_str()).is_relative() {
+                *path = dir.join(path.as_str()).to_string_lossy().into_owned();
+            }
+        };
+        for i in project.input.iter_mut() {
+            resolve(&mut i.path);
+        }
+        for o in project.output.iter_mut() {
+            resolve(&mut o.path);
+        }
+        let paths = project
+            .template
+            .iter_mut()
+            .chain(project.pnp.iter_mut())
+            .chain(project.pnp_out.iter_mut())
+            .chain(project.rules.iter_mut());
+        for path in paths {
+            resolve(path);
+        }
+        Ok(project)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_file() {
+        let project = Project::new("test_data/bomerge.toml").unwrap();
+        assert_eq!(project.metadata.project, "Test board");
+        assert_eq!(project.metadata.revision, "A");
+        assert_eq!(project.merge_key.as_deref(), Some("mpn"));
+        assert!(project.category_sheets);
+        assert!(!project.cover);
+
+        assert_eq!(project.input.len(), 2);
+        assert_eq!(project.input[0].path, "test_data/bom0.xlsx");
+        assert_eq!(project.input[0].multiplier, 2);
+        assert_eq!(project.input[0].variant.as_deref(), Some("full"));
+        assert_eq!(project.input[1].multiplier, 1);
+        assert_eq!(project.input[1].sheet, None);
+
+        assert_eq!(project.output[0].path, "test_data/out/merged.xlsx");
+        assert_eq!(project.output[1].format.as_deref(), Some("md"));
+
+        assert!(Project::new("test_data/missing.toml")
+            .unwrap_err()
+            .starts_with("Unable to read project test_data/missing.toml"));
+
+        // A misspelt key is an error, not a silent default
+        assert!(toml::from_str::<ProjectInput>("path = \"a.xlsx\"\nmultipler = 2").is_err());
+        assert!(toml::from_str::<ProjectOutput>("path = \"a.xlsx\"\nfromat = \"ods\"").is_err());
+    }
+}
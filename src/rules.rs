@@ -0,0 +1,616 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+use super::items::{Category, Item};
+use super::utils::designator_category;
+
+/// How much a rule violation matters, only errors fail a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        f.pad(s)
+    }
+}
+
+/// A check over the items of a BOM.
+pub trait Rule {
+    /// Name used in the rules file and in the report.
+    fn name(&self) -> &'static str;
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Rules off by default are enabled from the rules file.
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// Part, as designators or comment, and message for each violation.
+    fn check(&self, items: &[Item]) -> Vec<(String, String)>;
+}
+
+/// Rule failed by a part.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Violation {
+    pub rule: String,
+    pub severity: Severity,
+    pub part: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<8}{} [{}] {}",
+            self.severity, self.part, self.rule, self.message
+        )
+    }
+}
+
+/// Settings of a rule, the rule default when not given.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RuleConfig {
+    pub enabled: Option<bool>,
+    pub severity: Option<Severity>,
+}
+
+/// Rules settings, loaded from a TOML file like:
+///
+/// ```toml
+/// manufacturers = ["Yageo", "Murata", "Texas Instruments"]
+///
+/// [missing_description]
+/// enabled = false
+/// [footprint_category]
+/// severity = "error"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RulesConfig {
+    /// Approved manufacturers, for unknown_manufacturer. Any one when empty.
+    pub manufacturers: Vec<String>,
+    /// Footprints of semiconductor packages, not expected on passives.
+    pub ic_packages: Vec<String>,
+    /// Footprints of chip passives, not expected on ICs and transistors.
+    pub chip_packages: Vec<String>,
+    /// Category of designator prefixes of the project, for designator_category.
+    #[serde(skip)]
+    pub categories: Vec<(String, Category)>,
+    #[serde(flatten)]
+    pub rules: BTreeMap<String, RuleConfig>,
+}
+
+impl Default for RulesConfig {
+    fn default() -> RulesConfig {
+        let list = |l: &[&str]| l.iter().map(|m| m.to_string()).collect();
+        RulesConfig {
+            manufacturers: Vec::new(),
+            ic_packages: list(&[
+                "SOT", "SOIC", "SOP", "SSOP", "TSSOP", "MSOP", "QFN", "DFN", "QFP", "BGA", "DPAK",
+                "TO-",
+            ]),
+            chip_packages: list(&["0201", "0402", "0603", "0805", "1206", "1210", "2512"]),
+            categories: Vec::new(),
+            rules: BTreeMap::new(),
+        }
+    }
+}
+
+impl RulesConfig {
+    /// Load a rules file.
+    pub fn new(filename: &str) -> Result<RulesConfig, String> {
+        let text = match fs::read_to_string(filename) {
+            Ok(t) => t,
+            Err(error) => return Err(format!("Unable to read rules {}: {}", filename, error)),
+        };
+        toml::from_str(&text).map_err(|error| format!("Invalid rules {}: {}", filename, error))
+    }
+}
+
+/// Name of an item in the report: its designators, or the comment without them.
+fn part_name(item: &Item) -> String {
+    if item.designator.is_empty() {
+        item.comment.clone()
+    } else {
+        item.designator.join(", ")
+    }
+}
+
+/// Violations of a rule checked item by item.
+fn each<F: Fn(&Item) -> Option<String>>(items: &[Item], f: F) -> Vec<(String, String)> {
+    items
+        .iter()
+        .filter_map(|item| f(item).map(|msg| (part_name(item), msg)))
+        .collect()
+}
+
+fn is_passive(item: &Item) -> bool {
+    matches!(
+        item.category,
+        Category::Resistors | Category::Capacitors | Category::Inductors
+    )
+}
+
+/// Whether a token of the footprint starts with one of the packages. The KiCad
+/// library, like `Resistor_SMD:` in `Resistor_SMD:R_0603_1608Metric`, is left
+/// out, and the name is split on underscores and spaces.
+fn has_package(footprint: &str, packages: &[String]) -> bool {
+    let footprint = footprint.to_uppercase();
+    let name = footprint.rsplit(':').next().unwrap_or("");
+    name.split(|c: char| c == '_' || c.is_whitespace())
+        .any(|t| packages.iter().any(|p| t.starts_with(&p.to_uppercase())))
+}
+
+/// Placed parts without a footprint.
+pub struct MissingFootprint;
+
+impl Rule for MissingFootprint {
+    fn name(&self) -> &'static str {
+        "missing_footprint"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        each(items, |item| {
+            if !item.designator.is_empty() && item.footprint.trim().is_empty() {
+                Some(String::from("no footprint"))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Parts without a description, reported as info by default.
+pub struct MissingDescription;
+
+impl Rule for MissingDescription {
+    fn name(&self) -> &'static str {
+        "missing_description"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        each(items, |item| {
+            if item.description.trim().is_empty() {
+                Some(String::from("no description"))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Resistors, capacitors and inductors without a value.
+pub struct EmptyComment;
+
+impl Rule for EmptyComment {
+    fn name(&self) -> &'static str {
+        "empty_comment"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        each(items, |item| {
+            if is_passive(item) && item.comment.trim().is_empty() {
+                Some(format!("{:?} without value", item.category))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Passive values which do not parse as a number, like "abc" or "1.2.3k".
+pub struct UnparseableValue;
+
+impl Rule for UnparseableValue {
+    fn name(&self) -> &'static str {
+        "unparseable_value"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        each(items, |item| {
+            let comment = item.comment.trim();
+            if is_passive(item)
+                && item.is_fitted()
+                && !comment.is_empty()
+                && item.base_exp.0.is_nan()
+            {
+                Some(format!("value [{}] is not a number", comment))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Designators with an unknown prefix, like TP1, or merged with parts of
+/// another category.
+pub struct DesignatorCategory {
+    pub categories: Vec<(String, Category)>,
+}
+
+impl Rule for DesignatorCategory {
+    fn name(&self) -> &'static str {
+        "designator_category"
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        for item in items {
+            for d in item.designator.iter() {
+                let category = designator_category(d, &self.categories);
+                if category == Category::Invalid {
+                    out.push((d.clone(), String::from("unknown designator prefix")));
+                } else if category != item.category {
+                    out.push((
+                        d.clone(),
+                        format!("prefix not of {:?}, as {}", item.category, part_name(item)),
+                    ));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Passives on semiconductor packages, or ICs and transistors on chip ones.
+pub struct FootprintCategory {
+    pub ic_packages: Vec<String>,
+    pub chip_packages: Vec<String>,
+}
+
+impl Rule for FootprintCategory {
+    fn name(&self) -> &'static str {
+        "footprint_category"
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        each(items, |item| {
+            let unexpected = match item.category {
+                Category::IC | Category::Transistor => {
+                    has_package(&item.footprint, &self.chip_packages)
+                }
+                _ if is_passive(item) => has_package(&item.footprint, &self.ic_packages),
+                _ => false,
+            };
+            if unexpected {
+                Some(format!(
+                    "{:?} on {} footprint",
+                    item.category, item.footprint
+                ))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Manufacturers not in the approved list of the rules file.
+pub struct UnknownManufacturer {
+    pub manufacturers: Vec<String>,
+}
+
+impl Rule for UnknownManufacturer {
+    fn name(&self) -> &'static str {
+        "unknown_manufacturer"
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        if self.manufacturers.is_empty() {
+            return Vec::new();
+        }
+        let mut out = Vec::new();
+        for item in items {
+            for pn in item.part_numbers.iter() {
+                let known = self
+                    .manufacturers
+                    .iter()
+                    .any(|m| m.eq_ignore_ascii_case(pn.manufacturer.trim()));
+                if !pn.manufacturer.trim().is_empty() && !known {
+                    out.push((
+                        part_name(item),
+                        format!("manufacturer [{}] not approved", pn.manufacturer),
+                    ));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Fitted parts without a manufacturer part number, off by default.
+pub struct MissingMpn;
+
+impl Rule for MissingMpn {
+    fn name(&self) -> &'static str {
+        "missing_mpn"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn enabled(&self) -> bool {
+        false
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        each(items, |item| {
+            if !item.designator.is_empty()
+                && item.is_fitted()
+                && item.part_numbers.iter().all(|m| m.mpn.trim().is_empty())
+            {
+                Some(String::from("no manufacturer part number"))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Designators listed in more than one line.
+pub struct DuplicateDesignator;
+
+impl Rule for DuplicateDesignator {
+    fn name(&self) -> &'static str {
+        "duplicate_designator"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, items: &[Item]) -> Vec<(String, String)> {
+        let mut lines: BTreeMap<&str, Vec<&Item>> = BTreeMap::new();
+        for item in items {
+            for d in item.designator.iter() {
+                lines.entry(d.as_str()).or_default().push(item);
+            }
+        }
+        lines
+            .iter()
+            .filter(|(_, l)| l.len() > 1)
+            .map(|(d, l)| {
+                let values = l.iter().map(|m| m.comment.as_str()).collect::<Vec<_>>();
+                (
+                    d.to_string(),
+                    format!("in {} lines: {}", l.len(), values.join(", ")),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Rules to run, each with its severity.
+pub struct RuleSet {
+    rules: Vec<(Box<dyn Rule>, Severity)>,
+}
+
+impl RuleSet {
+    /// Built-in rules with their default settings.
+    pub fn new() -> RuleSet {
+        let config = RulesConfig::default();
+        Self::build(Self::builtin(&config), &config)
+    }
+
+    /// Built-in rules, enabled and with the severity set by the rules file.
+    /// Errors when the file names a rule which does not exist.
+    pub fn with_config(config: &RulesConfig) -> Result<RuleSet, String> {
+        let builtin = Self::builtin(config);
+        for name in config.rules.keys() {
+            if !builtin.iter().any(|m| m.name() == name) {
+                return Err(format!("Unknown rule [{}]", name));
+            }
+        }
+        Ok(Self::build(builtin, config))
+    }
+
+    fn builtin(config: &RulesConfig) -> Vec<Box<dyn Rule>> {
+        vec![
+            Box::new(MissingFootprint),
+            Box::new(MissingDescription),
+            Box::new(EmptyComment),
+            Box::new(UnparseableValue),
+            Box::new(DesignatorCategory {
+                categories: config.categories.clone(),
+            }),
+            Box::new(FootprintCategory {
+                ic_packages: config.ic_packages.clone(),
+                chip_packages: config.chip_packages.clone(),
+            }),
+            Box::new(UnknownManufacturer {
+                manufacturers: config.manufacturers.clone(),
+            }),
+            Box::new(MissingMpn),
+            Box::new(DuplicateDesignator),
+        ]
+    }
+
+    fn build(builtin: Vec<Box<dyn Rule>>, config: &RulesConfig) -> RuleSet {
+        let mut set = RuleSet { rules: Vec::new() };
+        for rule in builtin {
+            let cfg = config.rules.get(rule.name()).cloned().unwrap_or_default();
+            if cfg.enabled.unwrap_or_else(|| rule.enabled()) {
+                let severity = cfg.severity.unwrap_or_else(|| rule.severity());
+                set.add(rule, severity);
+            }
+        }
+        set
+    }
+
+    /// Add a rule, like a project specific one.
+    pub fn add(&mut self, rule: Box<dyn Rule>, severity: Severity) {
+        self.rules.push((rule, severity));
+    }
+
+    /// Violations of every rule, the most severe first.
+    pub fn check(&self, items: &[Item]) -> Vec<Violation> {
+        let mut out = Vec::new();
+        for (rule, severity) in self.rules.iter() {
+            for (part, message) in rule.check(items) {
+                out.push(Violation {
+                    rule: String::from(rule.name()),
+                    severity: *severity,
+                    part,
+                    message,
+                });
+            }
+        }
+        out.sort_by_key(|v| Reverse(v.severity));
+        out
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> RuleSet {
+        RuleSet::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::{DataParser, ParseOptions};
+    use crate::load::to_rows;
+
+    #[test]
+    fn test_rules() {
+        let data = DataParser::new(to_rows(&[
+            &[
+                "Designator",
+                "Comment",
+                "Footprint",
+                "Description",
+                "Manufacturer",
+            ],
+            &["R1", "10k", "SOT23", "Resistor", "Yageo"],
+            &["R2", "abc", "0603", "Resistor", "Acme"],
+            &["C1", "", "", "Capacitor", "Murata"],
+            &["U1", "LM1117", "0805", "", ""],
+            &["R1", "0R", "0603", "Jumper", "Yageo"],
+            &["R3", "1.2.3k", "0603", "Resistor", "Yageo"],
+            &["TP1", "Test point", "TP_1MM", "Test point", "Yageo"],
+            &[
+                "C2",
+                "100nF",
+                "Package_TO_SOT_SMD:SOT-23",
+                "Capacitor",
+                "Murata",
+            ],
+            &[
+                "U2",
+                "LM358",
+                "Resistor_SMD:R_0603_1608Metric",
+                "Op amp",
+                "Yageo",
+            ],
+            &[
+                "R4",
+                "1k",
+                "Resistor_SMD:R_0603_1608Metric",
+                "Resistor",
+                "Yageo",
+            ],
+        ]))
+        .unwrap();
+        let config: RulesConfig = toml::from_str(
+            r#"
+            manufacturers = ["Yageo", "Murata"]
+            [missing_description]
+            enabled = false
+            [footprint_category]
+            severity = "error"
+            "#,
+        )
+        .unwrap();
+        let violations = RuleSet::with_config(&config).unwrap().check(data.items());
+        let found = violations
+            .iter()
+            .map(|m| format!("{} {} {}", m.severity, m.rule, m.part))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                "error missing_footprint C1",
+                "error empty_comment C1",
+                "error unparseable_value R2",
+                "error unparseable_value R3",
+                "error footprint_category R1",
+                "error footprint_category U1",
+                "error footprint_category C2",
+                "error footprint_category U2",
+                "error duplicate_designator R1",
+                "warning designator_category TP1",
+                "warning unknown_manufacturer R2",
+            ]
+        );
+        assert_eq!(
+            violations[4].to_string(),
+            "error   R1 [footprint_category] Resistors on SOT23 footprint"
+        );
+
+        // Defaults: descriptions checked, any manufacturer
+        let violations = RuleSet::new().check(data.items());
+        assert!(violations
+            .iter()
+            .any(|m| m.rule == "missing_description" && m.severity == Severity::Info));
+        assert!(!violations.iter().any(|m| m.rule == "unknown_manufacturer"));
+
+        let config: RulesConfig = toml::from_str("[missing_foot]\nenabled = false").unwrap();
+        assert_eq!(
+            RuleSet::with_config(&config).err(),
+            Some(String::from("Unknown rule [missing_foot]"))
+        );
+
+        // Prefixes of the project
+        let options = ParseOptions {
+            categories: vec![(String::from("tp"), Category::Mechanicals)],
+            ..Default::default()
+        };
+        let data = DataParser::with_options(
+            to_rows(&[&["Designator", "Comment"], &["TP1, TP2", "Test point"]]),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(data.items()[0].category, Category::Mechanicals);
+        let config = RulesConfig {
+            categories: options.categories,
+            ..Default::default()
+        };
+        let violations = RuleSet::with_config(&config).unwrap().check(data.items());
+        assert!(!violations.iter().any(|m| m.rule == "designator_category"));
+    }
+}
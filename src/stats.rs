@@ -0,0 +1,262 @@
+use serde::Serialize;
+
+use super::items::{Category, Item, Mount, Side};
+
+/// Counters for a group of items, placements are the designators count.
+/// Parts of `Mixed` lines are counted neither as SMD nor as THT.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Counts {
+    /// Unique lines, after merge.
+    pub lines: usize,
+    pub placements: usize,
+    pub smd: usize,
+    pub tht: usize,
+    pub top: usize,
+    pub bottom: usize,
+}
+
+/// Counts of a category.
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryStats {
+    pub category: Category,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+/// Counts of an input file, before merging with the others.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceStats {
+    pub source: String,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+/// Stats of one or more BOMs, by category and by input file.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatsReport {
+    /// Variant of the merged BOM, none for the default one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    pub categories: Vec<CategoryStats>,
+    pub sources: Vec<SourceStats>,
+    pub total: Counts,
+}
+
+impl Counts {
+    fn add(&mut self, item: &Item) {
+        self.lines += 1;
+        self.placements += item.quantity;
+        match item.mount {
+            Some(Mount::Smd) => self.smd += item.quantity,
+            Some(Mount::Tht) => self.tht += item.quantity,
+            _ => (),
+        }
+        self.top += item.placements(Side::Top);
+        self.bottom += item.placements(Side::Bottom);
+    }
+
+    /// Changed counters as "name old -> new (+delta)".
+    fn changes(&self, previous: &Counts) -> Vec<String> {
+        [
+            ("lines", previous.lines, self.lines),
+            ("placements", previous.placements, self.placements),
+            ("smd", previous.smd, self.smd),
+            ("tht", previous.tht, self.tht),
+            ("top", previous.top, self.top),
+            ("bottom", previous.bottom, self.bottom),
+        ]
+        .iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(name, old, new)| {
+            format!(
+                "{} {} -> {} ({:+})",
+                name,
+                old,
+                new,
+                *new as i64 - *old as i64
+            )
+        })
+        .collect()
+    }
+}
+
+impl StatsReport {
+    /// Stats of the items of a BOM, already merged: parts found in more
+    /// inputs are a single line here.
+    pub fn new(items: &[Item]) -> StatsReport {
+        let mut report = StatsReport::default();
+        for item in items {
+            report.total.add(item);
+            match report
+                .categories
+                .iter_mut()
+                .find(|m| m.category == item.category)
+            {
+                Some(c) => c.counts.add(item),
+                None => {
+                    let mut c = CategoryStats {
+                        category: item.category.clone(),
+                        counts: Counts::default(),
+                    };
+                    c.counts.add(item);
+                    report.categories.push(c);
+                }
+            }
+        }
+        report
+            .categories
+            .sort_by(|a, b| a.category.cmp(&b.category));
+        report
+    }
+
+    /// Add the contribution of an input file, from its own merged items.
+    pub fn add_source(&mut self, source: &str, items: &[Item]) {
+        let mut counts = Counts::default();
+        for item in items {
+            counts.add(item);
+        }
+        self.sources.push(SourceStats {
+            source: String::from(source),
+            counts,
+        });
+    }
+
+    /// Human readable table, with the per file rows only for more than one
+    /// input and the variant name above it for a variant.
+    pub fn table(&self) -> String {
+        let row = |label: &str, c: &Counts| {
+            format!(
+                "{:<24}{:>7}{:>11}{:>6}{:>6}{:>6}{:>8}\n",
+                label, c.lines, c.placements, c.smd, c.tht, c.top, c.bottom
+            )
+        };
+        let mut out = match &self.variant {
+            Some(v) => format!("Variant {}\n", v),
+            None => String::new(),
+        };
+        out.push_str(&format!(
+            "{:<24}{:>7}{:>11}{:>6}{:>6}{:>6}{:>8}\n",
+            "Category", "Lines", "Placements", "SMD", "THT", "Top", "Bottom"
+        ));
+        for c in self.categories.iter() {
+            out.push_str(&row(&format!("{:?}", c.category), &c.counts));
+        }
+        out.push_str(&row("Total", &self.total));
+        if self.sources.len() > 1 {
+            out.push('\n');
+            for s in self.sources.iter() {
+                out.push_str(&row(&s.source, &s.counts));
+            }
+        }
+        out
+    }
+
+    /// Counts changed since a previous report, a line for each category.
+    pub fn diff(&self, previous: &StatsReport) -> String {
+        let counts = |r: &StatsReport, c: &Category| {
+            r.categories
+                .iter()
+                .find(|m| &m.category == c)
+                .map_or(Counts::default(), |m| m.counts.clone())
+        };
+        let mut categories = self
+            .categories
+            .iter()
+            .chain(previous.categories.iter())
+            .map(|m| m.category.clone())
+            .collect::<Vec<_>>();
+        categories.sort();
+        categories.dedup();
+
+        let mut out = String::new();
+        let mut row = |label: String, changes: Vec<String>| {
+            if !changes.is_empty() {
+                out.push_str(&format!("{}: {}\n", label, changes.join(", ")));
+            }
+        };
+        for c in categories.iter() {
+            row(
+                format!("{:?}", c),
+                counts(self, c).changes(&counts(previous, c)),
+            );
+        }
+        row(String::from("Total"), self.total.changes(&previous.total));
+        if out.is_empty() {
+            out.push_str("No changes in counts\n");
+        }
+        out
+    }
+
+    /// Machine readable report, as pretty printed JSON.
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|error| format!("Unable to serialize stats: {}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::items::DataParser;
+    use crate::load::Load;
+
+    #[test]
+    fn test_stats_report() {
+        let parse = || DataParser::new(Load::new("test_data/bom0.xlsx").unwrap()).unwrap();
+        let (bom, again) = (parse(), parse());
+        let lines = bom.items().len();
+        let mut report =
+            StatsReport::new(DataParser::combine(vec![parse(), parse()], true).items());
+        report.add_source("bom0.xlsx", bom.items());
+        report.add_source("again.xlsx", again.items());
+
+        let res = report
+            .categories
+            .iter()
+            .find(|m| m.category == Category::Resistors)
+            .unwrap();
+        // The same parts in both inputs are one line, with both placements
+        assert_eq!(res.counts.lines, 4);
+        assert_eq!(res.counts.placements, 32);
+        assert_eq!(res.counts.smd, 32);
+        assert_eq!(res.counts.top, 20);
+        assert_eq!(res.counts.bottom, 12);
+
+        assert_eq!(report.sources.len(), 2);
+        assert_eq!(report.sources[0].counts.lines, lines);
+        assert_eq!(report.total.lines, lines);
+        assert_eq!(
+            report.total.placements,
+            report.sources[0].counts.placements * 2
+        );
+        assert!(report.table().contains("again.xlsx"));
+        assert!(!report.table().contains("Variant"));
+        assert!(report
+            .to_json()
+            .unwrap()
+            .contains("\"category\": \"Resistors\""));
+        assert!(!report.to_json().unwrap().contains("\"variant\""));
+
+        report.variant = Some(String::from("lite"));
+        assert!(report.table().starts_with("Variant lite\n"));
+        assert!(report.to_json().unwrap().contains("\"variant\": \"lite\""));
+    }
+
+    #[test]
+    fn test_stats_diff() {
+        let data = DataParser::new(Load::new("test_data/bom0.xlsx").unwrap()).unwrap();
+        let previous = StatsReport::new(data.items());
+        assert_eq!(previous.diff(&previous), "No changes in counts\n");
+
+        let items = data
+            .items()
+            .iter()
+            .filter(|m| m.category != Category::Connectors)
+            .cloned()
+            .collect::<Vec<_>>();
+        let report = StatsReport::new(&items);
+        assert!(report
+            .diff(&previous)
+            .starts_with("Connectors: lines 2 -> 0 (-2), placements 2 -> 0 (-2), tht 2 -> 0 (-2), top 2 -> 0 (-2)\nTotal: lines"));
+    }
+}
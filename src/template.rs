@@ -0,0 +1,391 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+use super::items::{Category, Header, HeaderMap, Item, Side, SortKey};
+use super::utils::value_to_eng_notation;
+
+/// Cell style, colors are names ("cyan", "yellow"..) or "#RRGGBB".
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CellFormat {
+    pub bold: bool,
+    pub italic: bool,
+    pub text_wrap: bool,
+    pub border: bool,
+    pub center_across: bool,
+    pub font_size: Option<f64>,
+    pub font_color: Option<String>,
+    pub bg_color: Option<String>,
+}
+
+/// Output column, in template order.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Column {
+    /// Item field to show: quantity, designator, comment, footprint, description,
+    /// manufacturer, manufacturer_part_number, mount_technology, layer, extra
+    /// (all Code/Note columns) or the label of a single input column ("Code farnell").
+    pub field: String,
+    pub label: Option<String>,
+    pub width: Option<f64>,
+    pub format: Option<CellFormat>,
+}
+
+/// Sections of the cover sheet.
+pub const COVER_FIELDS: [&str; 7] = [
+    "project", "revision", "variant", "date", "author", "inputs", "stats",
+];
+
+/// Summary sheet emitted before the BOM.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Cover {
+    pub enabled: bool,
+    /// Sections in order: project, revision, variant, date, author, inputs, stats.
+    /// The variant is left out when not set.
+    pub fields: Vec<String>,
+    pub title: CellFormat,
+    pub label: CellFormat,
+}
+
+impl Default for Cover {
+    fn default() -> Cover {
+        Cover {
+            enabled: false,
+            fields: COVER_FIELDS.iter().map(|m| m.to_string()).collect(),
+            title: CellFormat {
+                bold: true,
+                font_size: Some(16.0),
+                ..Default::default()
+            },
+            label: CellFormat {
+                bold: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Output layout, loaded from a TOML file like:
+///
+/// ```toml
+/// category_rows = true
+/// [cover]
+/// enabled = true
+/// [header]
+/// bold = true
+/// bg_color = "cyan"
+/// [[columns]]
+/// field = "quantity"
+/// label = "Qty"
+/// width = 6
+/// [sort]
+/// Resistors = ["value", "footprint"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Template {
+    /// Emit a merged row with the category name before its items.
+    pub category_rows: bool,
+    /// Add a worksheet for each category after the combined one.
+    pub category_sheets: bool,
+    /// Add a worksheet for each board side, one for each assembly pass.
+    pub side_sheets: bool,
+    pub header: CellFormat,
+    pub category: CellFormat,
+    pub data: CellFormat,
+    pub columns: Vec<Column>,
+    pub cover: Cover,
+    /// Sort keys by category name, "default" applies to the ones not listed.
+    pub sort: BTreeMap<String, Vec<SortKey>>,
+}
+
+/// Where the value of an output column comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnSource {
+    Quantity,
+    /// Index in the header map of the parsed BOM.
+    Header(usize),
+}
+
+/// Column as laid out in the output, resolved against the parsed headers.
+#[derive(Debug, Clone)]
+pub struct OutColumn {
+    pub label: String,
+    pub source: ColumnSource,
+    pub width: Option<f64>,
+    pub format: Option<CellFormat>,
+}
+
+impl Default for Template {
+    fn default() -> Template {
+        let column = |field: &str| Column {
+            field: String::from(field),
+            label: None,
+            width: None,
+            format: None,
+        };
+
+        let by_value = vec![SortKey::Value, SortKey::Footprint, SortKey::Designator];
+        let by_comment = vec![SortKey::Comment, SortKey::Designator];
+
+        Template {
+            category_rows: true,
+            category_sheets: false,
+            side_sheets: false,
+            header: CellFormat {
+                bold: true,
+                font_size: Some(12.0),
+                bg_color: Some(String::from("cyan")),
+                ..Default::default()
+            },
+            category: CellFormat {
+                bold: true,
+                border: true,
+                center_across: true,
+                bg_color: Some(String::from("yellow")),
+                ..Default::default()
+            },
+            data: CellFormat {
+                text_wrap: true,
+                font_size: Some(10.0),
+                ..Default::default()
+            },
+            columns: vec![
+                Column {
+                    label: Some(String::from("Qty")),
+                    format: Some(CellFormat {
+                        bold: true,
+                        font_size: Some(12.0),
+                        bg_color: Some(String::from("lime")),
+                        ..Default::default()
+                    }),
+                    ..column("quantity")
+                },
+                column("designator"),
+                column("comment"),
+                column("footprint"),
+                column("description"),
+                column("manufacturer"),
+                column("manufacturer_part_number"),
+                column("mount_technology"),
+                column("layer"),
+                column("extra"),
+            ],
+            cover: Cover::default(),
+            sort: [
+                ("Resistors", by_value.clone()),
+                ("Capacitors", by_value.clone()),
+                ("Inductors", by_value.clone()),
+                ("Cristal", by_value.clone()),
+                ("Fuses", by_value),
+                ("IC", by_comment.clone()),
+                ("Transistor", by_comment.clone()),
+                ("Diode", by_comment),
+                ("default", vec![SortKey::Designator]),
+            ]
+            .iter()
+            .map(|(c, k)| (c.to_string(), k.clone()))
+            .collect(),
+        }
+    }
+}
+
+impl Template {
+    /// Load a template file, with its colors and cover fields checked.
+    pub fn new(filename: &str) -> Result<Template, String> {
+        let text = match fs::read_to_string(filename) {
+            Ok(t) => t,
+            Err(error) => return Err(format!("Unable to read template {}: {}", filename, error)),
+        };
+        let template: Template = match toml::from_str(&text) {
+            Ok(t) => t,
+            Err(error) => return Err(format!("Invalid template {}: {}", filename, error)),
+        };
+        match template.validate() {
+            Ok(()) => Ok(template),
+            Err(error) => Err(format!("Invalid template {}: {}", filename, error)),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let formats = [
+            &self.header,
+            &self.category,
+            &self.data,
+            &self.cover.title,
+            &self.cover.label,
+        ];
+        let columns = self.columns.iter().filter_map(|m| m.format.as_ref());
+        for f in formats.iter().copied().chain(columns) {
+            for c in f.font_color.iter().chain(f.bg_color.iter()) {
+                parse_color(c)?;
+            }
+        }
+        for f in self.cover.fields.iter() {
+            if !COVER_FIELDS.contains(&f.as_str()) {
+                return Err(format!("Invalid cover field [{}]", f));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sort keys for the items of a category.
+    pub fn sort_keys(&self, category: &Category) -> &[SortKey] {
+        let name = format!("{:?}", category);
+        self.sort
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(&name))
+            .or_else(|| self.sort.iter().find(|(c, _)| c.as_str() == "default"))
+            .map_or(&[], |(_, k)| k.as_slice())
+    }
+
+    /// Items of a category, sorted as configured.
+    pub fn items_of<'a>(&self, data: &'a [Item], category: &Category) -> Vec<&'a Item> {
+        let mut items = data
+            .iter()
+            .filter(|m| m.category == *category)
+            .collect::<Vec<_>>();
+        let keys = self.sort_keys(category);
+        items.sort_by(|a, b| a.compare(b, keys));
+        items
+    }
+
+    /// Resolve template columns against the headers found in the BOM,
+    /// columns which are missing in the source are left out.
+    pub fn columns(&self, headers: &[HeaderMap]) -> Vec<OutColumn> {
+        let mut out = Vec::new();
+        for col in self.columns.iter() {
+            let field = col.field.to_lowercase();
+            if field == "quantity" {
+                out.push(OutColumn {
+                    label: col.label.clone().unwrap_or_else(|| String::from("Qty")),
+                    source: ColumnSource::Quantity,
+                    width: col.width,
+                    format: col.format.clone(),
+                });
+                continue;
+            }
+
+            let key = match field.as_str() {
+                "designator" => Some(Header::Designator),
+                "comment" => Some(Header::Comment),
+                "footprint" => Some(Header::Footprint),
+                "description" => Some(Header::Description),
+                "manufacturer" => Some(Header::Manufacturer),
+                "manufacturer_part_number" | "mpn" => Some(Header::ManufacturerPartNumber),
+                "mount_technology" => Some(Header::MountTecnology),
+                "layer" => Some(Header::Layer),
+                "extra" => Some(Header::Extra),
+                _ => None,
+            };
+            let matched = headers
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| match key {
+                    Some(k) => m.key == k,
+                    None => m.label.eq_ignore_ascii_case(col.field.trim()),
+                })
+                .collect::<Vec<_>>();
+
+            for (n, hdr) in matched.iter() {
+                let label = match &col.label {
+                    Some(l) if matched.len() == 1 => l.clone(),
+                    _ => hdr.label.clone(),
+                };
+                out.push(OutColumn {
+                    label,
+                    source: ColumnSource::Header(*n),
+                    width: col.width,
+                    format: col.format.clone(),
+                });
+            }
+        }
+        out
+    }
+}
+
+const MAX_AUTO_WIDTH: f64 = 50.0;
+
+/// Column width, in characters, for the longest text in the column.
+pub fn auto_width(text_len: usize) -> f64 {
+    // Long lists, like designators, wrap instead of growing the column
+    (text_len as f64 * 1.1 + 2.0).min(MAX_AUTO_WIDTH)
+}
+
+/// Color name of a template, or "#RRGGBB", as "#rrggbb".
+pub fn parse_color(name: &str) -> Result<String, String> {
+    let rgb = match name.to_lowercase().as_str() {
+        "black" => "000000",
+        "blue" => "0000ff",
+        "brown" => "800000",
+        "cyan" => "00ffff",
+        "gray" | "grey" => "808080",
+        "green" => "008000",
+        "lime" => "00ff00",
+        "magenta" | "pink" => "ff00ff",
+        "navy" => "000080",
+        "orange" => "ff6600",
+        "purple" => "800080",
+        "red" => "ff0000",
+        "silver" => "c0c0c0",
+        "white" => "ffffff",
+        "yellow" => "ffff00",
+        hex => match u32::from_str_radix(hex.trim_start_matches('#'), 16) {
+            Ok(rgb) => return Ok(format!("#{:06x}", rgb)),
+            Err(_) => return Err(format!("Invalid color [{}]", name)),
+        },
+    };
+    Ok(format!("#{}", rgb))
+}
+
+/// Text of an item for the given column.
+pub fn cell_value(item: &Item, headers: &[HeaderMap], source: &ColumnSource) -> String {
+    let n = match source {
+        ColumnSource::Quantity => return item.quantity.to_string(),
+        ColumnSource::Header(n) => *n,
+    };
+    let hdr = &headers[n];
+    // Position of this column among the ones which fill the same item field
+    let alternate =
+        |same: &dyn Fn(&HeaderMap) -> bool| headers[..n].iter().filter(|m| same(m)).count();
+
+    match hdr.key {
+        Header::Quantity => item.quantity.to_string(),
+        Header::Designator => item.designator.join(", "),
+        Header::Comment => match item.measure_unit.as_str() {
+            // not populated parts and unparsed values keep the comment, like "NP (0R)"
+            _ if !item.is_fitted() || item.base_exp.0.is_nan() => item.comment.clone(),
+            // as value only for parts with a measure unit
+            "ohm" | "F" | "H" | "Hz" => {
+                value_to_eng_notation(item.base_exp.0, item.base_exp.1, item.measure_unit.as_str())
+                    .unwrap_or_else(|_| item.comment.clone())
+            }
+            _ => item.comment.clone(),
+        },
+        Header::Footprint => item.footprint.clone(),
+        Header::Description => item.description.clone(),
+        Header::Manufacturer => item
+            .part_numbers
+            .get(alternate(&|m| m.key == Header::Manufacturer))
+            .map_or(String::new(), |m| m.manufacturer.clone()),
+        Header::ManufacturerPartNumber => item
+            .part_numbers
+            .get(alternate(&|m| m.key == Header::ManufacturerPartNumber))
+            .map_or(String::new(), |m| m.mpn.clone()),
+        Header::MountTecnology => item.mount.map_or(String::new(), |m| m.to_string()),
+        Header::Layer => {
+            let (top, bottom) = (item.placements(Side::Top), item.placements(Side::Bottom));
+            match (top, bottom) {
+                (0, 0) => String::new(),
+                (_, 0) => Side::Top.to_string(),
+                (0, _) => Side::Bottom.to_string(),
+                _ => format!("Top {}, Bottom {}", top, bottom),
+            }
+        }
+        Header::Extra => item
+            .extra
+            .get(alternate(&|m| m.key == Header::Extra))
+            .map_or(String::new(), |m| m.value.clone()),
+    }
+}
@@ -1,14 +1,16 @@
 use super::items::Category;
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::cmp::Ordering;
 
+/// Unit of a component from its designator prefix: ohm, F, H or Hz.
 pub fn detect_measure_unit(comment: &str) -> String {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"^([KkR|C|L|Y])").unwrap();
     }
-    match RE.captures(comment.as_ref()) {
+    match RE.captures(comment) {
         None => String::from("unknow"),
-        Some(cc) => match cc.get(1).map_or("", |m| m.as_str()).as_ref() {
+        Some(cc) => match cc.get(1).map_or("", |m| m.as_str()) {
             "K" | "k" | "R" => String::from("ohm"),
             "C" => String::from("F"),
             "L" => String::from("H"),
@@ -18,7 +20,8 @@ pub fn detect_measure_unit(comment: &str) -> String {
     }
 }
 
-pub fn value_to_eng_notation(base: f32, exp: i32, unit: &str) -> String {
+/// Value as written in a BOM, like 4k7, 100nF or 10R, NP for negative bases.
+pub fn value_to_eng_notation(base: f32, exp: i32, unit: &str) -> Result<String, String> {
     let unitletter = match exp {
         12 => "G",
         6 => "M",
@@ -28,11 +31,11 @@ pub fn value_to_eng_notation(base: f32, exp: i32, unit: &str) -> String {
         -6 => "u",
         -9 => "n",
         -12 => "p",
-        _ => panic!("Invalid exp for conversion"),
+        _ => return Err(format!("Invalid exp for conversion [{}]", exp)),
     };
 
     if base < 0.0 {
-        return String::from("NP");
+        return Ok(String::from("NP"));
     }
 
     let mut value = format!("{}", base);
@@ -52,74 +55,136 @@ pub fn value_to_eng_notation(base: f32, exp: i32, unit: &str) -> String {
     } else {
         value = format!("{}{}{}", value, unitletter, unit);
     }
-    String::from(value)
+    Ok(value)
 }
 
+/// True for comments of not populated parts: NP, DNP and the like, alone
+/// or followed by the value, as in "NP (0R)".
+pub fn is_not_populated(comment: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"(?i)^\s*(NP|DNP|DNF|NF|NM)\b").unwrap();
+    }
+    RE.is_match(comment)
+}
+
+/// Base and exponent of a component value, like (4.7, 3) for "4k7". Not
+/// populated parts give a negative base and comments without a number, like
+/// "abc" or "1.2.3k", a NaN one.
 pub fn convert_comment_to_value(comment: &str) -> (f32, i32) {
-    if comment == "NP" {
+    if is_not_populated(comment) {
         return (-1.0, 0);
     }
 
-    let v = comment
-        .split(",")
-        .map(|item| item.trim())
-        .collect::<Vec<_>>();
-
-    let value = match v.get(0) {
-        None => panic!("No component value to parse"),
-        Some(v) => v,
-    };
+    let value = comment.split(',').next().unwrap_or_default().trim();
 
     lazy_static! {
         static ref VAL: Regex = Regex::new(r"^([0-9.,]*)([GMkKRmunp]?)([0-9.,]*)").unwrap();
     }
 
     match VAL.captures(value) {
-        None => panic!("Fail to parse component value"),
+        None => (f32::NAN, 0),
         Some(cc) => {
             let left = cc.get(1).map_or("", |m| m.as_str());
-            let mult = match cc.get(2).map_or("", |m| m.as_str()).as_ref() {
+            let mult = match cc.get(2).map_or("", |m| m.as_str()) {
                 "G" => 12,
                 "M" => 6,
                 "k" | "K" => 3,
-                "R" | "" => 1,
                 "m" => -3,
                 "u" => -6,
                 "n" => -9,
                 "p" => -12,
-                _ => panic!("Invalid number"),
+                _ => 1,
             };
             let right = cc.get(3).map_or("", |m| m.as_str());
+            if left.is_empty() && right.is_empty() {
+                return (f32::NAN, 0);
+            }
 
             let mut together;
             let left = left.replace(",", ".");
-            let right = if right == "" { "0" } else { right };
+            let right = if right.is_empty() { "0" } else { right };
 
             together = format!("{}.{}", left, right);
             if left.contains(".") {
                 together = format!("{}{}", left, right);
             }
 
-            let base = match together.parse::<f32>() {
-                Err(error) => panic!(
-                    "Invalid base number for convertion from string value to float {:?}",
-                    error
-                ),
-                Ok(v) => v,
-            };
+            match together.parse::<f32>() {
+                Ok(base) => (base, mult),
+                Err(_) => (f32::NAN, 0),
+            }
+        }
+    }
+}
+
+/// Component value as a plain number from the (base, exp) of `convert_comment_to_value`.
+pub fn base_exp_to_value(base: f32, exp: i32) -> f64 {
+    // exp 1 marks a value without multiplier, like 10R
+    let exp = if exp == 1 { 0 } else { exp };
+    base as f64 * 10f64.powi(exp)
+}
+
+/// Compare strings with digits runs as numbers, so R2 sorts before R10.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take = |it: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut n = String::new();
+                    while let Some(c) = it.peek().filter(|c| c.is_ascii_digit()) {
+                        n.push(*c);
+                        it.next();
+                    }
+                    n
+                };
+                let (na, nb) = (take(&mut a), take(&mut b));
+                let (ta, tb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+                let ord = ta.len().cmp(&tb.len()).then(ta.cmp(tb));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ord = x.to_ascii_uppercase().cmp(&y.to_ascii_uppercase());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
 
-            (base, mult)
+/// Escape text for XML and HTML content or attributes.
+pub fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
         }
     }
+    out
 }
 
+/// Category of the designator prefix, `Invalid` when it is not a known one.
 pub fn guess_category<S: AsRef<str>>(designator: S) -> Category {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"^([a-zA-Z_]{1,3})").unwrap();
     }
 
     match RE.captures(designator.as_ref()) {
-        None => Category::IVALID,
+        None => Category::Invalid,
         Some(cc) => match String::from(cc.get(1).map_or("", |m| m.as_str()))
             .to_uppercase()
             .as_ref()
@@ -137,11 +202,31 @@ pub fn guess_category<S: AsRef<str>>(designator: S) -> Category {
             "TR" => Category::Transformes,
             "Y" => Category::Cristal,
             "U" => Category::IC,
-            _ => panic!("Invalid category[{:#?}]", designator.as_ref()),
+            _ => Category::Invalid,
         },
     }
 }
 
+/// Category of the designator, from the prefixes of the project first, case
+/// insensitive, then from the built in ones.
+pub fn designator_category<S: AsRef<str>>(
+    designator: S,
+    categories: &[(String, Category)],
+) -> Category {
+    let designator = designator.as_ref();
+    let prefix = designator
+        .split(|c: char| !c.is_ascii_alphabetic() && c != '_')
+        .next()
+        .unwrap_or("");
+    match categories
+        .iter()
+        .find(|(p, _)| p.eq_ignore_ascii_case(prefix))
+    {
+        Some((_, category)) => category.clone(),
+        None => guess_category(designator),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,6 +293,21 @@ mod tests {
             let a = convert_comment_to_value(i.0);
             println!("({:.3}, {:3}, \"{}\"),", a.0, a.1, i.0);
         }
+
+        for c in ["abc", "1.2.3k", "R", ""].iter() {
+            assert!(convert_comment_to_value(c).0.is_nan(), "{}", c);
+        }
+    }
+
+    #[test]
+    fn test_is_not_populated() {
+        for c in ["NP", "np", "DNP", "NP (0R)", "NP(25MHz)", "DNF - 10k"].iter() {
+            assert!(is_not_populated(c), "{}", c);
+            assert_eq!(convert_comment_to_value(c), (-1.0, 0));
+        }
+        for c in ["10k", "NP0 100pF", "NPN", "100nF", ""].iter() {
+            assert!(!is_not_populated(c), "{}", c);
+        }
     }
 
     #[test]
@@ -265,13 +365,32 @@ mod tests {
         ];
 
         for i in data.iter() {
-            assert_eq!(value_to_eng_notation(i.0, i.1, i.2), i.3);
+            assert_eq!(value_to_eng_notation(i.0, i.1, i.2).unwrap(), i.3);
         }
         //assert_eq!(0, 1);
+        assert!(value_to_eng_notation(1.0, 2, "ohm").is_err());
     }
+    #[test]
+    fn test_natural_cmp() {
+        let mut data = vec!["R10", "R2", "r1", "C100", "R02a", "R2b", "R1"];
+        data.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(data, vec!["C100", "r1", "R1", "R2", "R02a", "R2b", "R10"]);
+
+        let mut values = [(4.7, 3), (10.0, 1), (1.0, 3), (100.0, 1), (10.0, 3)];
+        values.sort_by(|a, b| {
+            base_exp_to_value(a.0, a.1)
+                .partial_cmp(&base_exp_to_value(b.0, b.1))
+                .unwrap()
+        });
+        assert_eq!(
+            values,
+            [(10.0, 1), (100.0, 1), (1.0, 3), (4.7, 3), (10.0, 3)]
+        );
+    }
+
     #[test]
     fn test_detect_measure_unit() {
-        let test_data = vec![
+        let test_data = [
             ["C123", "F"],
             ["R123", "ohm"],
             ["L232", "H"],
@@ -284,4 +403,20 @@ mod tests {
             assert_eq!(detect_measure_unit(data[0]), data[1]);
         }
     }
+
+    #[test]
+    fn test_designator_category() {
+        let categories = vec![
+            (String::from("TP"), Category::Mechanicals),
+            (String::from("r"), Category::Fuses),
+        ];
+        assert_eq!(
+            designator_category("TP1", &categories),
+            Category::Mechanicals
+        );
+        assert_eq!(designator_category("R12", &categories), Category::Fuses);
+        assert_eq!(designator_category("RN1", &categories), Category::Resistors);
+        assert_eq!(designator_category("C1", &categories), Category::Capacitors);
+        assert_eq!(designator_category("TP1", &[]), Category::Invalid);
+    }
 }
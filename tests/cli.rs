@@ -0,0 +1,151 @@
+//! Exit codes of the command line: 1 for failed checks, 2 for errors in the
+//! arguments, the settings files or the inputs, never a panic.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn bomerge(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_bomerge"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap()
+}
+
+fn assert_fails(args: &[&str], message: &str) {
+    let out = bomerge(args);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(out.status.code(), Some(2), "{:?}:\n{}", args, stderr);
+    assert!(stderr.contains(message), "{:?}:\n{}", args, stderr);
+    assert!(!stderr.contains("panicked"), "{:?}:\n{}", args, stderr);
+}
+
+/// Directory of its own for each test and test run.
+fn test_dir(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("bomerge_{}_{}", test, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn settings_file(dir: &Path, name: &str, text: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, text).unwrap();
+    path
+}
+
+#[test]
+fn test_bad_settings_exit_code() {
+    let dir = test_dir("bad_settings");
+    assert_fails(
+        &["stats", "--project", "test_data/missing.toml"],
+        "Unable to read project test_data/missing.toml",
+    );
+
+    let project = settings_file(
+        &dir,
+        "project.toml",
+        "[[input]]\npath = \"bom0.xlsx\"\nsheets = 2\n",
+    );
+    assert_fails(
+        &["stats", "--project", project.to_str().unwrap()],
+        "Invalid project",
+    );
+
+    let project = settings_file(
+        &dir,
+        "categories.toml",
+        "[categories]\nTP = \"Probes\"\n[[input]]\npath = \"bom0.xlsx\"\n",
+    );
+    assert_fails(
+        &["stats", "--project", project.to_str().unwrap()],
+        "Invalid category [Probes]",
+    );
+
+    let template = settings_file(&dir, "template.toml", "[header]\nbg_color = \"cyann\"\n");
+    assert_fails(
+        &[
+            "merge",
+            "test_data/bom0.xlsx",
+            "-o",
+            "-",
+            "--template",
+            template.to_str().unwrap(),
+        ],
+        "Invalid color [cyann]",
+    );
+
+    let rules = settings_file(&dir, "rules.toml", "[missing_foot]\nenabled = false\n");
+    assert_fails(
+        &[
+            "check",
+            "test_data/bom0.xlsx",
+            "--rules",
+            rules.to_str().unwrap(),
+        ],
+        "Unknown rule [missing_foot]",
+    );
+
+    assert_fails(
+        &[
+            "check",
+            "test_data/bom0.xlsx",
+            "--pnp",
+            "test_data/missing.pos",
+        ],
+        "Unable to read pick and place test_data/missing.pos",
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_read_only_commands() {
+    let dir = test_dir("read_only");
+    let root = env!("CARGO_MANIFEST_DIR");
+    let project = settings_file(
+        &dir,
+        "outputs.toml",
+        &format!(
+            "pnp = \"{0}/test_data/pnp0.csv\"\npnp_out = \"fitted.csv\"\n\
+             [[input]]\npath = \"{0}/test_data/bom0.xlsx\"\n\
+             [[output]]\npath = \"merged.xlsx\"\n",
+            root
+        ),
+    );
+    for command in ["stats", "check"].iter() {
+        let out = bomerge(&[command, "--project", project.to_str().unwrap()]);
+        assert_ne!(out.status.code(), Some(2), "{}", command);
+    }
+    assert!(!dir.join("merged.xlsx").exists());
+    assert!(!dir.join("fitted.csv").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_project_without_command() {
+    let dir = test_dir("project_merge");
+    let root = env!("CARGO_MANIFEST_DIR");
+    let project = settings_file(
+        &dir,
+        "merge.toml",
+        &format!(
+            "[[input]]\npath = \"{}/test_data/bom0.xlsx\"\n\
+             [[output]]\npath = \"merged.csv\"\n",
+            root
+        ),
+    );
+    let project = project.to_str().unwrap();
+
+    let out = bomerge(&["--project", project, "stats"]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(!dir.join("merged.csv").exists());
+
+    let out = bomerge(&["-q", "--project", project]);
+    assert_eq!(out.status.code(), Some(0));
+    assert!(dir.join("merged.csv").exists());
+
+    assert_fails(&["-q"], "a command or --project is required");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,100 @@
+//! Run the whole pipeline on every `test_data/*.xlsx` and compare the merged
+//! workbook, read back with calamine, against the CSV snapshots in
+//! `tests/golden`. Set `BOMERGE_UPDATE_GOLDEN=1` to regenerate them.
+
+use calamine::{open_workbook_auto, Reader};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn to_csv(rows: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    for row in rows {
+        let line = row
+            .iter()
+            .map(|c| {
+                if c.contains(',') || c.contains('"') || c.contains('\n') {
+                    format!("\"{}\"", c.replace('"', "\"\""))
+                } else {
+                    c.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+fn read_xlsx(path: &Path) -> Vec<Vec<String>> {
+    let mut wk = open_workbook_auto(path).unwrap();
+    let mut rows = Vec::new();
+    for sheet in wk.sheet_names().to_owned() {
+        let range = wk.worksheet_range(&sheet).unwrap().unwrap();
+        rows.push(vec![format!("## {}", sheet)]);
+        for row in range.rows() {
+            rows.push(row.iter().map(|c| c.to_string()).collect());
+        }
+    }
+    rows
+}
+
+fn merge(bom: &Path, name: &str) -> Vec<Vec<String>> {
+    let out_dir = env::temp_dir().join(format!("bomerge_golden_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_bomerge"))
+        .arg("merge")
+        .arg(bom)
+        .current_dir(&out_dir)
+        .output()
+        .unwrap();
+    assert!(
+        status.status.success(),
+        "bomerge failed on {:?}:\n{}",
+        bom,
+        String::from_utf8_lossy(&status.stderr)
+    );
+
+    let rows = read_xlsx(&out_dir.join("merged_bom.xlsx"));
+    fs::remove_dir_all(&out_dir).unwrap();
+    rows
+}
+
+#[test]
+fn test_golden_xlsx() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let update = env::var("BOMERGE_UPDATE_GOLDEN").is_ok();
+
+    let mut boms = fs::read_dir(root.join("test_data"))
+        .unwrap()
+        .map(|m| m.unwrap().path())
+        .filter(|m| m.extension().is_some_and(|e| e == "xlsx"))
+        .collect::<Vec<_>>();
+    boms.sort();
+
+    let mut failed = Vec::new();
+    for bom in boms.iter() {
+        let name = bom.file_stem().unwrap().to_str().unwrap();
+        let golden = root.join("tests/golden").join(format!("{}.csv", name));
+        let csv = to_csv(&merge(bom, name));
+
+        if update {
+            fs::write(&golden, &csv).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&golden) {
+            Ok(expected) if expected == csv => {}
+            Ok(_) => failed.push(format!("{}: output differs from {:?}", name, golden)),
+            Err(_) => failed.push(format!("{}: missing golden {:?}", name, golden)),
+        }
+    }
+
+    assert!(
+        failed.is_empty(),
+        "{}\nrun with BOMERGE_UPDATE_GOLDEN=1 to regenerate the golden files",
+        failed.join("\n")
+    );
+}